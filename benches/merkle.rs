@@ -0,0 +1,40 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use halo2::pasta::Fp;
+
+use halo2_semaphore::primitives::{
+    merkle::MerkleTree,
+    poseidon::{ConstantLength, Hash},
+    HashSpec,
+};
+
+const DEPTH: usize = 20;
+
+fn leaves() -> Vec<Fp> {
+    (0..1u64 << DEPTH).map(Fp::from).collect()
+}
+
+fn sequential_root(leaves: &[Fp]) -> Fp {
+    let mut level = leaves.to_vec();
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| Hash::init(HashSpec, ConstantLength::<2>).hash([pair[0], pair[1]]))
+            .collect();
+    }
+    level[0]
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let leaves = leaves();
+
+    let mut group = c.benchmark_group("merkle-tree-1M-leaves");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| b.iter(|| sequential_root(&leaves)));
+    group.bench_function("from_leaves", |b| {
+        b.iter(|| MerkleTree::from_leaves(DEPTH, &leaves).root())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use criterion::{criterion_group, BatchSize, BenchmarkId, Criterion};
+use halo2::{
+    pasta::{EqAffine, Fp},
+    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof},
+    poly::commitment::Params,
+    transcript::{Blake2bRead, Blake2bWrite, Challenge255},
+};
+use serde_json::{json, Value};
+
+use halo2_semaphore::{
+    dev::circuit_stats,
+    primitives::{
+        merkle::MerkleTree,
+        poseidon::{ConstantLength, PoseidonHasher},
+        HashSpec,
+    },
+    witness::identity_commitment,
+    SemaphoreCircuit,
+};
+
+// The IDs of the benchmarks registered in this run, which are collected into the JSON
+// summary.
+static REGISTERED: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+/// Records the benchmark `parameter` of `group`, and returns its ID.
+fn register(group: &str, parameter: impl ToString) -> BenchmarkId {
+    let parameter = parameter.to_string();
+    REGISTERED.lock().unwrap().push(format!("{}/{}", group, parameter));
+    BenchmarkId::from_parameter(parameter)
+}
+
+fn bench_poseidon(c: &mut Criterion) {
+    let hasher = PoseidonHasher::<Fp, HashSpec, _, 3, 2>::new(ConstantLength::<2>);
+    let message = [Fp::from(1), Fp::from(2)];
+    REGISTERED.lock().unwrap().push("poseidon".to_string());
+    c.bench_function("poseidon", |b| b.iter(|| hasher.hash(message)));
+}
+
+fn bench_tree(c: &mut Criterion) {
+    // Inserts the 1025th leaf of a tree holding 1024 leaves.
+    let leaves: Vec<_> = (1..=1024u64).map(Fp::from).collect();
+
+    let mut group = c.benchmark_group("merkle-tree-insert");
+    for depth in [16, 20, 32] {
+        let tree = MerkleTree::from_leaves(depth, &leaves);
+        group.bench_function(register("merkle-tree-insert", depth), |b| {
+            b.iter_batched(
+                || tree.clone(),
+                |mut tree| {
+                    tree.set(leaves.len(), Fp::from(42));
+                    tree
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_circuit<const DEPTH: usize>(c: &mut Criterion) {
+    let k = circuit_stats::<DEPTH>().k;
+    let params: Params<EqAffine> = Params::new(k);
+
+    let (trapdoor, nullifier) = (Fp::from(2), Fp::from(3));
+    let tree = MerkleTree::from_leaves(DEPTH, &[identity_commitment(trapdoor, nullifier)]);
+    let (circuit, public_inputs) =
+        SemaphoreCircuit::<Fp, DEPTH>::from_tree(trapdoor, nullifier, b"topic", &tree, 0);
+    let instance = public_inputs.to_instance();
+    let empty_circuit = SemaphoreCircuit::<Fp, DEPTH>::default();
+
+    let mut group = c.benchmark_group("keygen_vk");
+    group.sample_size(10);
+    group.bench_function(register("keygen_vk", DEPTH), |b| {
+        b.iter(|| keygen_vk(&params, &empty_circuit).unwrap())
+    });
+    group.finish();
+
+    let vk = keygen_vk(&params, &empty_circuit).unwrap();
+    let mut group = c.benchmark_group("keygen_pk");
+    group.sample_size(10);
+    group.bench_function(register("keygen_pk", DEPTH), |b| {
+        b.iter_batched(
+            || keygen_vk(&params, &empty_circuit).unwrap(),
+            |vk| keygen_pk(&params, vk, &empty_circuit).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+
+    let pk = keygen_pk(&params, vk, &empty_circuit).unwrap();
+    let prove = |circuit: SemaphoreCircuit<Fp, DEPTH>| {
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_proof(&params, &pk, &[circuit], &[&[&instance]], &mut transcript).unwrap();
+        transcript.finalize()
+    };
+    let mut group = c.benchmark_group("create_proof");
+    group.sample_size(10);
+    group.bench_function(register("create_proof", DEPTH), |b| {
+        b.iter(|| prove(circuit.clone()))
+    });
+    group.finish();
+
+    let proof = prove(circuit);
+    let mut group = c.benchmark_group("verify_proof");
+    group.bench_function(register("verify_proof", DEPTH), |b| {
+        b.iter(|| {
+            let msm = params.empty_msm();
+            let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+            let guard =
+                verify_proof(&params, pk.get_vk(), msm, &[&[&instance]], &mut transcript).unwrap();
+            assert!(guard.use_challenges().eval());
+        })
+    });
+    group.finish();
+}
+
+fn bench_circuits(c: &mut Criterion) {
+    bench_circuit::<4>(c);
+    bench_circuit::<16>(c);
+    bench_circuit::<20>(c);
+    bench_circuit::<32>(c);
+}
+
+criterion_group!(benches, bench_poseidon, bench_tree, bench_circuits);
+
+/// Returns the directory criterion writes its reports to.
+fn criterion_dir() -> PathBuf {
+    match env::var_os("CRITERION_HOME") {
+        Some(home) => home.into(),
+        None => Path::new(&env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| "target".into()))
+            .join("criterion"),
+    }
+}
+
+/// Collects the mean and standard deviation, in nanoseconds, of every benchmark this run
+/// registered and measured since `start`. Benchmarks skipped by a filter keep the
+/// estimates of an earlier run, which are left out.
+fn collect_estimates(dir: &Path, start: SystemTime) -> Vec<Value> {
+    REGISTERED
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|id| {
+            let path = dir.join(id).join("new").join("estimates.json");
+            if fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()? < start {
+                return None;
+            }
+            let estimates: Value = serde_json::from_str(&fs::read_to_string(&path).ok()?).ok()?;
+            Some(json!({
+                "id": id,
+                "mean_ns": estimates["mean"]["point_estimate"],
+                "std_dev_ns": estimates["std_dev"]["point_estimate"],
+            }))
+        })
+        .collect()
+}
+
+/// Writes the results of this run to `semaphore.json` in the criterion directory, tagged
+/// with the current commit so runs can be compared across commits.
+fn write_summary(start: SystemTime) {
+    let dir = criterion_dir();
+    let results = collect_estimates(&dir, start);
+
+    let commit = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string());
+    let summary = json!({ "commit": commit, "results": results });
+
+    let path = dir.join("semaphore.json");
+    fs::write(&path, serde_json::to_string_pretty(&summary).unwrap()).unwrap();
+    println!("wrote {}", path.display());
+}
+
+fn main() {
+    let start = SystemTime::now();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+    write_summary(start);
+}
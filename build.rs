@@ -0,0 +1,19 @@
+fn main() {
+    // Compile the C harness of `tests/ffi.rs` against the public header. It is only
+    // linked by that test, which declares it with `#[link]` and needs this feature.
+    #[cfg(feature = "c-harness")]
+    {
+        println!("cargo:rerun-if-changed=include/semaphore.h");
+        println!("cargo:rerun-if-changed=tests/ffi/harness.c");
+        cc::Build::new()
+            .file("tests/ffi/harness.c")
+            .include("include")
+            .warnings_into_errors(true)
+            .cargo_metadata(false)
+            .compile("semaphore_harness");
+        println!(
+            "cargo:rustc-link-search=native={}",
+            std::env::var("OUT_DIR").unwrap()
+        );
+    }
+}
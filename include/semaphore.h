@@ -0,0 +1,63 @@
+/*
+ * C ABI of halo2-semaphore. See src/ffi.rs for the details of each function.
+ *
+ * Field elements are 32 bytes, little endian. An identity is 64 bytes: its trapdoor
+ * followed by its nullifier. Buffers returned through an out pointer belong to the
+ * caller, who releases them with semaphore_buffer_free.
+ */
+
+#ifndef SEMAPHORE_H
+#define SEMAPHORE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef enum SemaphoreStatus {
+  SEMAPHORE_OK = 0,
+  SEMAPHORE_NULL_POINTER = 1,
+  SEMAPHORE_INVALID_INPUT = 2,
+  SEMAPHORE_NOT_MEMBER = 3,
+  SEMAPHORE_PROVER_ERROR = 4,
+  SEMAPHORE_MALFORMED_PROOF = 5,
+  SEMAPHORE_CIRCUIT_MISMATCH = 6,
+  SEMAPHORE_INVALID_PROOF = 7,
+} SemaphoreStatus;
+
+typedef struct SemaphoreBuffer {
+  uint8_t *data;
+  size_t len;
+} SemaphoreBuffer;
+
+/* Derives an identity from a secret, and writes its 64 bytes to out. */
+SemaphoreStatus semaphore_identity_new(const uint8_t *secret, size_t secret_len,
+                                       SemaphoreBuffer *out);
+
+/* Writes the 32-byte identity commitment of a 64-byte identity to out. */
+SemaphoreStatus semaphore_commitment(const uint8_t *identity, size_t identity_len,
+                                     SemaphoreBuffer *out);
+
+/*
+ * Proves that identity is the member at index of the group whose 32-byte commitments
+ * are leaves, and signals on topic. Writes the serialized proof to out. The leaves of
+ * removed members are zero, and no identity can prove at their index.
+ */
+SemaphoreStatus semaphore_prove(const uint8_t *identity, size_t identity_len,
+                                const uint8_t *leaves, size_t leaves_len, size_t index,
+                                const uint8_t *topic, size_t topic_len,
+                                SemaphoreBuffer *out);
+
+/* Verifies a serialized proof. */
+SemaphoreStatus semaphore_verify(const uint8_t *proof, size_t proof_len);
+
+/* Releases a buffer returned by this library, and resets it to empty. */
+void semaphore_buffer_free(SemaphoreBuffer *buffer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SEMAPHORE_H */
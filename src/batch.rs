@@ -0,0 +1,179 @@
+//! Checking a batch of Semaphore proofs at once.
+//!
+//! Verifying a halo2 proof ends with an inner product argument whose final check is a
+//! multiscalar multiplication linear in the circuit size, and dominates the cost of
+//! verification. [`BatchVerifier`] runs the rest of each verifier, accumulates the
+//! pending checks of all proofs into a random linear combination, and evaluates it once.
+//! It also folds the public inputs of the batch into a single commitment, see
+//! [`inputs_commitment`].
+
+use blake2b_simd::{Params as Blake2bParams, State};
+use halo2::{
+    arithmetic::FieldExt,
+    pasta::{EqAffine, Fp},
+    plonk::{verify_proof, Error, VerifyingKey},
+    poly::commitment::{Params, MSM},
+    transcript::{Blake2bRead, Challenge255},
+};
+
+use crate::{
+    primitives::{poseidon::CachedSpec, HashSpec},
+    witness::{hash, PublicInputs},
+};
+
+/// Blake2b personalisation for the coefficients of the batch check.
+const BATCH_PERSONALIZATION: &[u8; 16] = b"Semaphore_Batch_";
+
+/// Returns a commitment to the public inputs of a batch of signals, in order.
+///
+/// Each signal is hashed to `H(H(external_nullifier, nullifier_hash), root)`, and the
+/// batch to `H(... H(H(0, s_0), s_1) ..., s_{n-1})`.
+pub fn inputs_commitment<F: FieldExt>(inputs: &[PublicInputs<F>]) -> F
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    inputs.iter().fold(F::zero(), |acc, inputs| {
+        let signal = hash([
+            hash([inputs.external_nullifier, inputs.nullifier_hash]),
+            inputs.root,
+        ]);
+        hash([acc, signal])
+    })
+}
+
+/// Verifies Semaphore proofs over the Pallas base field in a batch.
+///
+/// The coefficient of each proof in the batch check is derived from a Blake2b hash of all
+/// proofs and public inputs added so far, including its own, so a prover cannot choose
+/// proofs whose errors cancel out.
+#[derive(Debug)]
+pub struct BatchVerifier<'a> {
+    params: &'a Params<EqAffine>,
+    vk: &'a VerifyingKey<EqAffine>,
+    msm: MSM<'a, EqAffine>,
+    transcript: State,
+    inputs: Vec<PublicInputs<Fp>>,
+}
+
+impl<'a> BatchVerifier<'a> {
+    /// Starts an empty batch of proofs for the circuit with the given verifying key.
+    pub fn new(params: &'a Params<EqAffine>, vk: &'a VerifyingKey<EqAffine>) -> Self {
+        BatchVerifier {
+            params,
+            vk,
+            msm: params.empty_msm(),
+            transcript: Blake2bParams::new()
+                .hash_length(64)
+                .personal(BATCH_PERSONALIZATION)
+                .to_state(),
+            inputs: vec![],
+        }
+    }
+
+    /// Adds a proof to the batch.
+    ///
+    /// This fails if the proof is malformed; an invalid proof that is well formed is only
+    /// rejected by [`BatchVerifier::finalize`].
+    pub fn add(&mut self, public_inputs: PublicInputs<Fp>, proof: &[u8]) -> Result<(), Error> {
+        let instance = public_inputs.to_instance();
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+        let guard = verify_proof(
+            self.params,
+            self.vk,
+            self.params.empty_msm(),
+            &[&[&instance]],
+            &mut transcript,
+        )?;
+
+        self.transcript.update(&(proof.len() as u64).to_le_bytes());
+        self.transcript.update(proof);
+        for value in &instance {
+            self.transcript.update(&value.to_bytes());
+        }
+        let mut bytes = [0; 64];
+        bytes.copy_from_slice(self.transcript.clone().finalize().as_bytes());
+
+        self.msm.scale(Fp::from_bytes_wide(&bytes));
+        self.msm.add_msm(&guard.use_challenges());
+        self.inputs.push(public_inputs);
+        Ok(())
+    }
+
+    /// Returns the number of proofs in the batch.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Checks every proof of the batch, and returns the [`inputs_commitment`] of their
+    /// public inputs if they are all valid.
+    pub fn finalize(self) -> Result<Fp, Error> {
+        if self.msm.eval() {
+            Ok(inputs_commitment(&self.inputs))
+        } else {
+            Err(Error::ConstraintSystemFailure)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{
+        pasta::{EqAffine, Fp},
+        plonk::{create_proof, keygen_pk, keygen_vk},
+        poly::commitment::Params,
+        transcript::{Blake2bWrite, Challenge255},
+    };
+
+    use super::{inputs_commitment, BatchVerifier};
+    use crate::{
+        dev::circuit_stats, primitives::merkle::MerkleTree, witness::identity_commitment,
+        SemaphoreCircuit, MERKLE_DEPTH,
+    };
+
+    #[test]
+    fn batch_verify() {
+        let params: Params<EqAffine> = Params::new(circuit_stats::<MERKLE_DEPTH>().k);
+        let empty_circuit = SemaphoreCircuit::<Fp, MERKLE_DEPTH>::default();
+        let vk = keygen_vk(&params, &empty_circuit).unwrap();
+        let pk = keygen_pk(&params, vk, &empty_circuit).unwrap();
+
+        let identities: Vec<_> = (0..2).map(|i| (Fp::from(10 + i), Fp::from(20 + i))).collect();
+        let leaves: Vec<_> = identities
+            .iter()
+            .map(|(trapdoor, nullifier)| identity_commitment(*trapdoor, *nullifier))
+            .collect();
+        let tree = MerkleTree::from_leaves(MERKLE_DEPTH, &leaves);
+
+        let signals: Vec<_> = identities
+            .iter()
+            .enumerate()
+            .map(|(index, (trapdoor, nullifier))| {
+                let (circuit, public_inputs) = SemaphoreCircuit::<Fp, MERKLE_DEPTH>::from_tree(
+                    *trapdoor, *nullifier, b"topic", &tree, index,
+                );
+                let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+                create_proof(&params, &pk, &[circuit], &[&[&public_inputs.to_instance()]], &mut transcript)
+                    .unwrap();
+                (public_inputs, transcript.finalize())
+            })
+            .collect();
+        let inputs: Vec<_> = signals.iter().map(|(public_inputs, _)| *public_inputs).collect();
+
+        let mut batch = BatchVerifier::new(&params, pk.get_vk());
+        for (public_inputs, proof) in &signals {
+            batch.add(*public_inputs, proof).unwrap();
+        }
+        assert_eq!(batch.len(), 2);
+        assert_eq!(batch.finalize(), Ok(inputs_commitment(&inputs)));
+
+        // Swapping the proofs of the two signals fails the batch.
+        let mut batch = BatchVerifier::new(&params, pk.get_vk());
+        batch.add(signals[0].0, &signals[1].1).unwrap();
+        batch.add(signals[1].0, &signals[0].1).unwrap();
+        assert!(batch.finalize().is_err());
+    }
+}
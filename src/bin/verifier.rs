@@ -0,0 +1,263 @@
+//! An HTTP service that verifies Semaphore proofs.
+//!
+//! ```text
+//! semaphore-verifier <key> [--addr <host:port>] [--roots <file>] [--unique-nullifiers]
+//! ```
+//!
+//! `<key>` holds the parameters and verifying key written by `halo2-semaphore keys`.
+//! `--roots` only accepts proofs against the roots listed in the file, one in hex per
+//! line, and `--unique-nullifiers` rejects a nullifier hash seen in an accepted proof.
+//!
+//! `POST /verify` takes `{"proof": "<hex>"}` and `POST /verify-batch` takes
+//! `{"proofs": ["<hex>", ...]}`, where each proof is a serialized `SemaphoreProof` in
+//! hex. Both answer `{"valid": true}`, the batch with its `inputs_commitment`, or
+//! `{"valid": false, "error": "..."}`, the batch with the `index` of the rejected proof.
+//! Request bodies over 1 MiB are refused with 413.
+
+use std::{
+    env, fs,
+    io::{self, Read},
+    process,
+    sync::Arc,
+    thread,
+};
+
+use halo2::{arithmetic::FieldExt, pasta::Fp};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+use halo2_semaphore::{
+    nullifier::NullifierStore,
+    proof::{from_hex, read_verifying_key, to_hex, SemaphoreProof},
+    verifier::Verifier,
+    MERKLE_DEPTH,
+};
+
+/// The largest request body read, enough for a batch of 200 proofs in hex.
+const MAX_BODY_LEN: usize = 1 << 20;
+
+const USAGE: &str = "usage: semaphore-verifier <key> [--addr <host:port>] [--roots <file>] [--unique-nullifiers]";
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1)
+}
+
+fn read_roots(path: &str) -> Vec<Fp> {
+    let roots = fs::read_to_string(path).unwrap_or_else(|error| fail(&format!("{}: {}", path, error)));
+    roots
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            from_hex(line)
+                .and_then(|bytes| bytes.try_into().ok())
+                .and_then(|bytes| Option::from(Fp::from_bytes(&bytes)))
+                .unwrap_or_else(|| fail(&format!("{}: not a root: {}", path, line)))
+        })
+        .collect()
+}
+
+fn proof(value: &Value) -> Result<SemaphoreProof, String> {
+    let bytes = value
+        .as_str()
+        .and_then(from_hex)
+        .ok_or_else(|| "a proof is a hex string".to_string())?;
+    SemaphoreProof::from_bytes(&bytes).map_err(|error| error.to_string())
+}
+
+/// Reads a request body of at most [`MAX_BODY_LEN`] bytes, given the length the client
+/// announced, if any.
+fn read_body(reader: impl Read, length: Option<usize>) -> Result<String, (u16, Value)> {
+    let too_large = || (413, json!({ "error": format!("body is over {} bytes", MAX_BODY_LEN) }));
+    if length.is_some_and(|length| length > MAX_BODY_LEN) {
+        return Err(too_large());
+    }
+    let mut body = String::new();
+    reader
+        .take(MAX_BODY_LEN as u64 + 1)
+        .read_to_string(&mut body)
+        .map_err(|error: io::Error| (400, json!({ "error": error.to_string() })))?;
+    if body.len() > MAX_BODY_LEN {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+/// Answers a request with a status code and a JSON body.
+fn handle(verifier: &Verifier, method: &Method, url: &str, body: &str) -> (u16, Value) {
+    if url != "/verify" && url != "/verify-batch" {
+        return (404, json!({ "error": "not found" }));
+    }
+    if *method != Method::Post {
+        return (405, json!({ "error": "method not allowed" }));
+    }
+    let body: Value = match serde_json::from_str(body) {
+        Ok(body) => body,
+        Err(error) => return (400, json!({ "error": error.to_string() })),
+    };
+
+    if url == "/verify" {
+        let result = proof(&body["proof"]).and_then(|proof| verifier.verify(&proof).map_err(|error| error.to_string()));
+        return match result {
+            Ok(()) => (200, json!({ "valid": true })),
+            Err(error) => (200, json!({ "valid": false, "error": error })),
+        };
+    }
+
+    let proofs = match body["proofs"].as_array() {
+        Some(proofs) => proofs,
+        None => return (400, json!({ "error": "proofs is not an array" })),
+    };
+    let mut batch = Vec::with_capacity(proofs.len());
+    for (index, value) in proofs.iter().enumerate() {
+        match proof(value) {
+            Ok(proof) => batch.push(proof),
+            Err(error) => return (200, json!({ "valid": false, "index": index, "error": error })),
+        }
+    }
+    match verifier.verify_batch(&batch) {
+        Ok(commitment) => (200, json!({ "valid": true, "inputs_commitment": to_hex(&commitment.to_bytes()) })),
+        Err(error) => (200, json!({ "valid": false, "index": error.index, "error": error.error.to_string() })),
+    }
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let key = args.next().unwrap_or_else(|| fail(USAGE));
+    let mut addr = "127.0.0.1:8080".to_string();
+    let mut roots = None;
+    let mut unique_nullifiers = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--addr" => addr = args.next().unwrap_or_else(|| fail(USAGE)),
+            "--roots" => roots = Some(read_roots(&args.next().unwrap_or_else(|| fail(USAGE)))),
+            "--unique-nullifiers" => unique_nullifiers = true,
+            _ => fail(USAGE),
+        }
+    }
+
+    let mut file = fs::File::open(&key).unwrap_or_else(|error| fail(&format!("{}: {}", key, error)));
+    let (params, vk) = read_verifying_key::<MERKLE_DEPTH, _>(&mut file)
+        .unwrap_or_else(|error| fail(&format!("{}: {}", key, error)));
+    let mut verifier = Verifier::<MERKLE_DEPTH>::new(params, vk);
+    if let Some(roots) = roots {
+        verifier = verifier.with_roots(roots);
+    }
+    if unique_nullifiers {
+        verifier = verifier.with_nullifier_store(NullifierStore::new());
+    }
+    let verifier = Arc::new(verifier);
+
+    let server = Arc::new(Server::http(&addr).unwrap_or_else(|error| fail(&format!("{}: {}", addr, error))));
+    println!("verifying proofs of circuit {} on http://{}", verifier.circuit_id(), addr);
+
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    let handles: Vec<_> = (0..workers)
+        .map(|_| {
+            let (server, verifier) = (server.clone(), verifier.clone());
+            thread::spawn(move || {
+                for mut request in server.incoming_requests() {
+                    let length = request.body_length();
+                    let (status, response) = match read_body(request.as_reader(), length) {
+                        Ok(body) => handle(&verifier, request.method(), request.url(), &body),
+                        Err(error) => error,
+                    };
+                    let header = Header::from_bytes("Content-Type", "application/json").unwrap();
+                    let response = Response::from_string(response.to_string())
+                        .with_status_code(status)
+                        .with_header(header);
+                    if let Err(error) = request.respond(response) {
+                        eprintln!("{}", error);
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::pasta::Fp;
+    use serde_json::{json, Value};
+    use tiny_http::Method;
+
+    use super::{handle, read_body, MAX_BODY_LEN};
+    use halo2_semaphore::{
+        fixtures::{Fixture, SEED},
+        proof::{keygen, prove, read_verifying_key, to_hex, write_verifying_key},
+        verifier::Verifier,
+        MERKLE_DEPTH,
+    };
+
+    /// A verifier for the fixture keys, and a proof of the fixture in hex.
+    fn setup() -> (Verifier, String) {
+        let (params, pk) = keygen::<MERKLE_DEPTH>().unwrap();
+        let fixture = Fixture::<Fp, MERKLE_DEPTH>::new(SEED);
+        let proof = prove(&params, &pk, fixture.circuit.clone(), fixture.public_inputs).unwrap();
+
+        let mut key = vec![];
+        write_verifying_key(&params, pk.get_vk(), &mut key).unwrap();
+        let (params, vk) = read_verifying_key::<MERKLE_DEPTH, _>(&mut &key[..]).unwrap();
+        (Verifier::new(params, vk), to_hex(&proof.to_bytes()))
+    }
+
+    fn post(verifier: &Verifier, url: &str, body: &Value) -> (u16, Value) {
+        handle(verifier, &Method::Post, url, &body.to_string())
+    }
+
+    #[test]
+    fn routes() {
+        let (verifier, proof) = setup();
+
+        assert_eq!(
+            handle(&verifier, &Method::Post, "/", "{}"),
+            (404, json!({ "error": "not found" }))
+        );
+        assert_eq!(
+            handle(&verifier, &Method::Get, "/verify", ""),
+            (405, json!({ "error": "method not allowed" }))
+        );
+        let (status, body) = handle(&verifier, &Method::Post, "/verify", "not json");
+        assert_eq!(status, 400);
+        assert!(body["error"].is_string());
+        assert_eq!(
+            post(&verifier, "/verify-batch", &json!({ "proofs": proof })),
+            (400, json!({ "error": "proofs is not an array" }))
+        );
+
+        assert_eq!(
+            post(&verifier, "/verify", &json!({ "proof": proof })),
+            (200, json!({ "valid": true }))
+        );
+        let (status, body) = post(&verifier, "/verify", &json!({ "proof": "00" }));
+        assert_eq!(status, 200);
+        assert_eq!(body["valid"], json!(false));
+        assert!(body["error"].is_string());
+
+        let (status, body) = post(&verifier, "/verify-batch", &json!({ "proofs": [proof, proof] }));
+        assert_eq!(status, 200);
+        assert_eq!(body["valid"], json!(true));
+        assert_eq!(body["inputs_commitment"].as_str().map(str::len), Some(64));
+
+        assert_eq!(
+            post(&verifier, "/verify-batch", &json!({ "proofs": [proof, 1] })),
+            (200, json!({ "valid": false, "index": 1, "error": "a proof is a hex string" }))
+        );
+    }
+
+    #[test]
+    fn body_limit() {
+        assert_eq!(read_body(&b"{}"[..], Some(2)), Ok("{}".to_string()));
+        let body = vec![b' '; MAX_BODY_LEN];
+        assert_eq!(read_body(&body[..], None).map(|body| body.len()), Ok(MAX_BODY_LEN));
+
+        let body = vec![b' '; MAX_BODY_LEN + 1];
+        assert_eq!(read_body(&body[..], None).unwrap_err().0, 413);
+        // An announced length is refused before reading.
+        assert_eq!(read_body(&b"{}"[..], Some(MAX_BODY_LEN + 1)).unwrap_err().0, 413);
+    }
+}
@@ -0,0 +1,516 @@
+//! Anonymous credentials: proving a predicate on a hidden attribute of a group member.
+//!
+//! A member's attributes (age bracket, country, role, ...) are the leaves of a small
+//! Merkle tree, each leaf `H(index, value)` binding a value to its slot. The member's
+//! credential commitment `H(H(trapdoor, nullifier), attributes_root)` takes the place of
+//! the identity commitment in the group tree. [`CredentialCircuit`] proves membership as
+//! [`SemaphoreCircuit`] does, and additionally reveals whether one attribute satisfies a
+//! [`Predicate`], without revealing the attribute itself.
+
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Layouter, SimpleFloorPlanner},
+    plonk::{Circuit, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use crate::{
+    gadget::{
+        comparison::{ComparisonInstructions, LessThanChip, LessThanConfig},
+        hash::HashInstructions,
+        is_zero::{IsZeroChip, IsZeroConfig, IsZeroInstructions},
+        merkle::MerklePath,
+        range_check::{LookupRangeCheckChip, LookupRangeCheckConfig, RangeCheckInstructions},
+    },
+    primitives::{
+        hash_to_field::external_nullifier, merkle::MerkleTree, poseidon::CachedSpec,
+        poseidon::Spec, HashSpec,
+    },
+    utils::{copy, CellValue, UtilitiesInstructions, Var},
+    witness::{hash, identity_commitment, merkle_witness, nullifier_hash, PublicInputs},
+    Config, SemaphoreCircuit, EXTERNAL_NULLIFIER, MERKLE_DEPTH, NULLIFIER_HASH, ROOT,
+};
+
+/// The depth of the attributes tree. A credential holds `2^ATTRIBUTE_DEPTH` attributes.
+pub const ATTRIBUTE_DEPTH: usize = 2;
+
+/// The number of bits of attributes compared by [`Predicate::InRange`].
+pub const ATTRIBUTE_BITS: usize = 64;
+
+// Range checks are done in eight words of eight bits.
+const WORD_BITS: usize = 8;
+const NUM_WORDS: usize = ATTRIBUTE_BITS / WORD_BITS;
+
+type AttributeRangeCheck<F> = LookupRangeCheckChip<F, WORD_BITS, NUM_WORDS>;
+
+// Absolute offsets for the public inputs following those of `SemaphoreCircuit`.
+const ATTRIBUTE: usize = 3;
+const RESULT: usize = 4;
+const OPERANDS: usize = 5;
+
+/// Returns the leaf of the attributes tree holding `value` in slot `index`.
+pub fn attribute_leaf<F: FieldExt>(index: usize, value: F) -> F
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    hash([F::from_u64(index as u64), value])
+}
+
+/// Returns the attributes tree of a member. Slots past the given attributes hold zero.
+///
+/// # Panics
+///
+/// Panics if there are more than `2^ATTRIBUTE_DEPTH` attributes.
+pub fn attributes_tree<F: FieldExt>(attributes: &[F]) -> MerkleTree<F>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    assert!(attributes.len() <= 1 << ATTRIBUTE_DEPTH, "too many attributes");
+
+    let leaves: Vec<_> = (0..1 << ATTRIBUTE_DEPTH)
+        .map(|index| attribute_leaf(index, attributes.get(index).copied().unwrap_or_else(F::zero)))
+        .collect();
+    MerkleTree::from_leaves(ATTRIBUTE_DEPTH, &leaves)
+}
+
+/// Returns the credential commitment inserted into the group tree for an identity with
+/// the given attributes root.
+pub fn credential_commitment<F: FieldExt>(
+    identity_trapdoor: F,
+    identity_nullifier: F,
+    attributes_root: F,
+) -> F
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    hash([identity_commitment(identity_trapdoor, identity_nullifier), attributes_root])
+}
+
+/// The kind of predicate a [`CredentialCircuit`] proves.
+///
+/// The kind is part of the circuit's shape, so each kind has its own verifying key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PredicateKind {
+    #[default]
+    Equal,
+    InRange,
+}
+
+/// A predicate on an attribute, whose operands are public.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Predicate<F: FieldExt> {
+    /// The attribute equals the given value.
+    Equal(F),
+    /// The attribute lies in `[lower, upper)`. The attribute must fit in
+    /// `ATTRIBUTE_BITS` bits.
+    InRange(u64, u64),
+}
+
+impl<F: FieldExt> Predicate<F> {
+    pub fn kind(&self) -> PredicateKind {
+        match self {
+            Predicate::Equal(_) => PredicateKind::Equal,
+            Predicate::InRange(..) => PredicateKind::InRange,
+        }
+    }
+
+    /// Returns the operands of the predicate, as they appear in the instance column.
+    pub fn operands(&self) -> Vec<F> {
+        match self {
+            Predicate::Equal(value) => vec![*value],
+            Predicate::InRange(lower, upper) => vec![F::from_u64(*lower), F::from_u64(*upper)],
+        }
+    }
+
+    /// Evaluates the predicate on `value`.
+    pub fn holds(&self, value: F) -> bool {
+        match self {
+            Predicate::Equal(target) => value == *target,
+            Predicate::InRange(lower, upper) => {
+                F::from_u64(*lower) <= value && value < F::from_u64(*upper)
+            }
+        }
+    }
+}
+
+/// Configuration for a [`CredentialCircuit`].
+#[derive(Clone, Debug)]
+pub struct CredentialConfig<F: FieldExt> {
+    semaphore: Config<F>,
+    is_zero: IsZeroConfig,
+    less_than: LessThanConfig<LookupRangeCheckConfig>,
+    s_in_range: Selector,
+}
+
+/// A Semaphore signal from a member holding a credential, revealing whether one of
+/// its attributes satisfies a predicate.
+///
+/// Its instance column holds the public inputs of [`SemaphoreCircuit`], where the group
+/// tree's leaves are credential commitments, followed by the attribute slot, the
+/// predicate's result and the predicate's operands. The slot and the operands are
+/// public, the attribute value is not.
+#[derive(Clone, Debug, Default)]
+pub struct CredentialCircuit<F: FieldExt, const DEPTH: usize = MERKLE_DEPTH> {
+    pub identity_trapdoor: Option<F>,
+    pub identity_nullifier: Option<F>,
+    pub external_nullifier: Option<F>,
+    pub position_bits: Option<[F; DEPTH]>,
+    pub path: Option<[F; DEPTH]>,
+    pub attribute_index: Option<F>,
+    pub attribute: Option<F>,
+    pub attribute_position_bits: Option<[F; ATTRIBUTE_DEPTH]>,
+    pub attribute_path: Option<[F; ATTRIBUTE_DEPTH]>,
+    pub kind: PredicateKind,
+    // The target of `Equal`, or the bounds of `InRange`.
+    pub operands: [Option<F>; 2],
+}
+
+impl<F: FieldExt, const DEPTH: usize> UtilitiesInstructions<F> for CredentialCircuit<F, DEPTH> {
+    type Var = CellValue<F>;
+}
+
+impl<F: FieldExt, const DEPTH: usize> CredentialCircuit<F, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    // Returns a cell holding one if `lower <= attribute < upper`, and zero otherwise.
+    fn in_range(
+        &self,
+        config: &CredentialConfig<F>,
+        mut layouter: impl Layouter<F>,
+        attribute: CellValue<F>,
+        lower: CellValue<F>,
+        upper: CellValue<F>,
+    ) -> Result<CellValue<F>, Error> {
+        let range_check = AttributeRangeCheck::<F>::construct(config.less_than.range_check.clone());
+        range_check.range_check(layouter.namespace(|| "range check attribute"), attribute)?;
+        range_check.range_check(layouter.namespace(|| "range check lower"), lower)?;
+        range_check.range_check(layouter.namespace(|| "range check upper"), upper)?;
+
+        let less_than = LessThanChip::<F, AttributeRangeCheck<F>>::construct(config.less_than.clone());
+        let below = less_than.less_than(layouter.namespace(|| "attribute < lower"), attribute, lower)?;
+        let under = less_than.less_than(layouter.namespace(|| "attribute < upper"), attribute, upper)?;
+
+        let advices = config.semaphore.advices;
+        layouter.assign_region(
+            || "in range",
+            |mut region| {
+                config.s_in_range.enable(&mut region, 0)?;
+                copy(&mut region, || "below", advices[0], 0, &below)?;
+                copy(&mut region, || "under", advices[1], 0, &under)?;
+
+                let value = below
+                    .value()
+                    .zip(under.value())
+                    .map(|(below, under)| (F::one() - below) * under);
+                let cell = region.assign_advice(
+                    || "in range",
+                    advices[2],
+                    0,
+                    || value.ok_or(Error::SynthesisError),
+                )?;
+                Ok(CellValue::new(cell, value))
+            },
+        )
+    }
+}
+
+impl<F: FieldExt, const DEPTH: usize> Circuit<F> for CredentialCircuit<F, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    type Config = CredentialConfig<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        CredentialCircuit {
+            kind: self.kind,
+            ..Self::default()
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let semaphore = SemaphoreCircuit::<F, DEPTH>::configure(meta);
+        let advices = semaphore.advices;
+
+        let is_zero = IsZeroChip::configure(meta, advices);
+
+        let table = meta.lookup_table_column();
+        let range_check = AttributeRangeCheck::<F>::configure(meta, advices[3], table);
+        let less_than = LessThanChip::<F, AttributeRangeCheck<F>>::configure(meta, advices, range_check);
+
+        // The attribute is in range when it is not below the lower bound and is under the
+        // upper bound.
+        let s_in_range = meta.selector();
+        meta.create_gate("in range", |meta| {
+            let s_in_range = meta.query_selector(s_in_range);
+            let below = meta.query_advice(advices[0], Rotation::cur());
+            let under = meta.query_advice(advices[1], Rotation::cur());
+            let result = meta.query_advice(advices[2], Rotation::cur());
+            vec![s_in_range * ((Expression::Constant(F::one()) - below) * under - result)]
+        });
+
+        CredentialConfig {
+            semaphore,
+            is_zero,
+            less_than,
+            s_in_range,
+        }
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let advices = config.semaphore.advices;
+        let instance = config.semaphore.instance;
+
+        AttributeRangeCheck::<F>::construct(config.less_than.range_check.clone())
+            .load_table(layouter.namespace(|| "load range check table"))?;
+
+        let identity_trapdoor = self.load_private(
+            layouter.namespace(|| "witness identity_trapdoor"),
+            advices[0],
+            self.identity_trapdoor,
+        )?;
+        let identity_nullifier = self.load_private(
+            layouter.namespace(|| "witness identity_nullifier"),
+            advices[0],
+            self.identity_nullifier,
+        )?;
+        let external_nullifier = self.load_private(
+            layouter.namespace(|| "witness external nullifier"),
+            advices[0],
+            self.external_nullifier,
+        )?;
+        let attribute_index = self.load_private(
+            layouter.namespace(|| "witness attribute_index"),
+            advices[0],
+            self.attribute_index,
+        )?;
+        let attribute =
+            self.load_private(layouter.namespace(|| "witness attribute"), advices[0], self.attribute)?;
+
+        let poseidon_chip = config.semaphore.construct_poseidon_chip();
+        let identity_commitment = poseidon_chip.hash(
+            layouter.namespace(|| "hash to identity commitment"),
+            [identity_trapdoor, identity_nullifier],
+        )?;
+        let nullifier_hash = poseidon_chip.hash(
+            layouter.namespace(|| "hash to nullifier hash"),
+            [identity_nullifier, external_nullifier],
+        )?;
+        let attribute_leaf = poseidon_chip.hash(
+            layouter.namespace(|| "hash to attribute leaf"),
+            [attribute_index, attribute],
+        )?;
+
+        let attributes_path = MerklePath {
+            chip: config.semaphore.construct_merkle_chip(),
+            leaf_pos: self.attribute_position_bits,
+            path: self.attribute_path,
+        };
+        let attributes_root = attributes_path.calculate_root(
+            layouter.namespace(|| "attributes root calculation"),
+            attribute_leaf,
+        )?;
+        let credential_commitment = poseidon_chip.hash(
+            layouter.namespace(|| "hash to credential commitment"),
+            [identity_commitment, attributes_root],
+        )?;
+
+        let merkle_inputs = MerklePath {
+            chip: config.semaphore.construct_merkle_chip(),
+            leaf_pos: self.position_bits,
+            path: self.path,
+        };
+        let root = merkle_inputs.calculate_root(
+            layouter.namespace(|| "merkle root calculation"),
+            credential_commitment,
+        )?;
+
+        let result = match self.kind {
+            PredicateKind::Equal => {
+                let target = self.load_private(
+                    layouter.namespace(|| "witness target"),
+                    advices[0],
+                    self.operands[0],
+                )?;
+                self.expose_public(layouter.namespace(|| "constrain target"), instance, target, OPERANDS)?;
+
+                IsZeroChip::construct(config.is_zero.clone())
+                    .is_equal(layouter.namespace(|| "attribute == target"), attribute, target)?
+            }
+            PredicateKind::InRange => {
+                let lower =
+                    self.load_private(layouter.namespace(|| "witness lower"), advices[0], self.operands[0])?;
+                let upper =
+                    self.load_private(layouter.namespace(|| "witness upper"), advices[0], self.operands[1])?;
+                self.expose_public(layouter.namespace(|| "constrain lower"), instance, lower, OPERANDS)?;
+                self.expose_public(layouter.namespace(|| "constrain upper"), instance, upper, OPERANDS + 1)?;
+
+                self.in_range(&config, layouter.namespace(|| "lower <= attribute < upper"), attribute, lower, upper)?
+            }
+        };
+
+        self.expose_public(layouter.namespace(|| "constrain external_nullifier"), instance, external_nullifier, EXTERNAL_NULLIFIER)?;
+        self.expose_public(layouter.namespace(|| "constrain nullifier_hash"), instance, nullifier_hash, NULLIFIER_HASH)?;
+        self.expose_public(layouter.namespace(|| "constrain root"), instance, root, ROOT)?;
+        self.expose_public(layouter.namespace(|| "constrain attribute_index"), instance, attribute_index, ATTRIBUTE)?;
+        self.expose_public(layouter.namespace(|| "constrain result"), instance, result, RESULT)
+    }
+}
+
+/// The public inputs of [`CredentialCircuit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CredentialPublicInputs<F: FieldExt> {
+    pub semaphore: PublicInputs<F>,
+    pub attribute_index: usize,
+    pub predicate: Predicate<F>,
+    pub result: bool,
+}
+
+impl<F: FieldExt> CredentialPublicInputs<F> {
+    /// Returns the instance column expected by [`CredentialCircuit`].
+    pub fn to_instance(&self) -> Vec<F> {
+        let mut instance = self.semaphore.to_instance();
+        instance.push(F::from_u64(self.attribute_index as u64));
+        instance.push(F::from(self.result));
+        instance.extend(self.predicate.operands());
+        instance
+    }
+}
+
+impl<F: FieldExt, const DEPTH: usize> CredentialCircuit<F, DEPTH>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Builds the witness for the member at `index` in `tree`, holding `attributes`,
+    /// signalling on `topic` and proving `predicate` on the attribute in slot
+    /// `attribute_index`, together with the matching public inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tree` is not of depth `DEPTH`, if the leaf at `index` is not the
+    /// credential commitment of the given identity and attributes, if `attribute_index`
+    /// is not a slot of the attributes tree, or if an `InRange` predicate is evaluated on
+    /// an attribute of more than `ATTRIBUTE_BITS` bits.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_tree(
+        identity_trapdoor: F,
+        identity_nullifier: F,
+        attributes: &[F],
+        topic: &[u8],
+        tree: &MerkleTree<F>,
+        index: usize,
+        attribute_index: usize,
+        predicate: Predicate<F>,
+    ) -> (Self, CredentialPublicInputs<F>) {
+        assert!(attribute_index < 1 << ATTRIBUTE_DEPTH, "no attribute slot {}", attribute_index);
+
+        let attribute = attributes.get(attribute_index).copied().unwrap_or_else(F::zero);
+        if let Predicate::InRange(..) = predicate {
+            assert!(
+                attribute.to_bytes()[ATTRIBUTE_BITS / 8..].iter().all(|byte| *byte == 0),
+                "attribute {} does not fit in {} bits",
+                attribute_index,
+                ATTRIBUTE_BITS
+            );
+        }
+
+        let attributes_tree = attributes_tree(attributes);
+        let (attribute_path, attribute_position_bits) = merkle_witness(
+            attribute_leaf(attribute_index, attribute),
+            &attributes_tree,
+            attribute_index,
+        );
+        let commitment =
+            credential_commitment(identity_trapdoor, identity_nullifier, attributes_tree.root());
+        let (path, position_bits) = merkle_witness(commitment, tree, index);
+
+        let external_nullifier = external_nullifier(topic);
+        let public_inputs = CredentialPublicInputs {
+            semaphore: PublicInputs {
+                external_nullifier,
+                nullifier_hash: nullifier_hash(identity_nullifier, external_nullifier),
+                root: tree.root(),
+            },
+            attribute_index,
+            predicate,
+            result: predicate.holds(attribute),
+        };
+
+        let operands = predicate.operands();
+        let circuit = CredentialCircuit {
+            identity_trapdoor: Some(identity_trapdoor),
+            identity_nullifier: Some(identity_nullifier),
+            external_nullifier: Some(external_nullifier),
+            position_bits: Some(position_bits),
+            path: Some(path),
+            attribute_index: Some(F::from_u64(attribute_index as u64)),
+            attribute: Some(attribute),
+            attribute_position_bits: Some(attribute_position_bits),
+            attribute_path: Some(attribute_path),
+            kind: predicate.kind(),
+            operands: [operands.first().copied(), operands.get(1).copied()],
+        };
+
+        (circuit, public_inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{dev::MockProver, pasta::Fp};
+
+    use super::{attributes_tree, credential_commitment, CredentialCircuit, Predicate};
+    use crate::{primitives::merkle::MerkleTree, MERKLE_DEPTH};
+
+    const AGE: usize = 0;
+    const COUNTRY: usize = 1;
+
+    #[test]
+    fn predicate_on_hidden_attribute() {
+        let (trapdoor, nullifier) = (Fp::from(2), Fp::from(3));
+        let attributes = [Fp::from(30), Fp::from(250)];
+        let commitment = credential_commitment(trapdoor, nullifier, attributes_tree(&attributes).root());
+        let tree = MerkleTree::from_leaves(MERKLE_DEPTH, &[Fp::from(7), commitment]);
+
+        let prove = |attribute_index, predicate| {
+            CredentialCircuit::<Fp>::from_tree(
+                trapdoor,
+                nullifier,
+                &attributes,
+                b"topic",
+                &tree,
+                1,
+                attribute_index,
+                predicate,
+            )
+        };
+
+        for (attribute_index, predicate, result) in [
+            (COUNTRY, Predicate::Equal(Fp::from(250)), true),
+            (COUNTRY, Predicate::Equal(Fp::from(251)), false),
+            (AGE, Predicate::InRange(18, 65), true),
+            (AGE, Predicate::InRange(30, 31), true),
+            (AGE, Predicate::InRange(31, 100), false),
+            (AGE, Predicate::InRange(0, 30), false),
+        ] {
+            let (circuit, public_inputs) = prove(attribute_index, predicate);
+            assert_eq!(public_inputs.result, result);
+
+            let prover = MockProver::run(10, &circuit, vec![public_inputs.to_instance()]).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            // The prover cannot claim the opposite result.
+            let mut other = public_inputs;
+            other.result = !result;
+            let prover = MockProver::run(10, &circuit, vec![other.to_instance()]).unwrap();
+            assert!(prover.verify().is_err());
+
+            // Nor a predicate on another slot.
+            let mut other = public_inputs;
+            other.attribute_index = 3 - attribute_index;
+            let prover = MockProver::run(10, &circuit, vec![other.to_instance()]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Developer tools for measuring circuits.
+
+use halo2::{
+    arithmetic::FieldExt,
+    dev::CircuitCost,
+    pasta::{vesta, Fp},
+    plonk::{
+        Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error, Fixed,
+        FloorPlanner, Instance, Selector,
+    },
+};
+
+use crate::{
+    multi_group::MultiGroupCircuit,
+    primitives::{poseidon::Spec, HashSpec},
+    Config, SemaphoreCircuit,
+};
+
+#[cfg(feature = "dev-graph")]
+mod graph;
+#[cfg(feature = "dev-graph")]
+pub use graph::write_semaphore_graphs;
+
+/// An [`Assignment`] that only records the highest row touched by the circuit.
+#[derive(Default)]
+struct RowCounter {
+    rows: usize,
+}
+
+impl RowCounter {
+    fn touch(&mut self, row: usize) {
+        self.rows = self.rows.max(row + 1);
+    }
+}
+
+impl<F: FieldExt> Assignment<F> for RowCounter {
+    fn enter_region<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn exit_region(&mut self) {}
+
+    fn enable_selector<A, AR>(&mut self, _: A, _: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        Ok(())
+    }
+
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Option<F>, Error> {
+        Ok(None)
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Advice>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Result<VR, Error>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Fixed>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Result<VR, Error>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        Ok(())
+    }
+
+    fn copy(
+        &mut self,
+        _: Column<Any>,
+        left_row: usize,
+        _: Column<Any>,
+        right_row: usize,
+    ) -> Result<(), Error> {
+        self.touch(left_row);
+        self.touch(right_row);
+        Ok(())
+    }
+
+    fn fill_from_row(
+        &mut self,
+        _: Column<Fixed>,
+        _: usize,
+        _: Option<Assigned<F>>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+/// Returns the number of columns or selectors `allocate` has already created in `cs`.
+///
+/// halo2 does not expose these counts, so the next one is allocated in a copy of `cs`
+/// and matched against freshly allocated ones, which compare equal by index.
+fn count_allocated<F: FieldExt, T: PartialEq>(
+    cs: &ConstraintSystem<F>,
+    allocate: impl Fn(&mut ConstraintSystem<F>) -> T,
+) -> usize {
+    let next = allocate(&mut cs.clone());
+    let mut fresh = ConstraintSystem::default();
+    (0..).find(|_| allocate(&mut fresh) == next).unwrap()
+}
+
+/// A circuit that reports the fixed columns it enables for constants.
+///
+/// The floor planner places the circuit's constants in these columns, after the rows its
+/// regions use there, so [`used_rows`] needs them to count those rows. halo2 does not
+/// expose them from a [`ConstraintSystem`].
+pub trait ConstantColumns<F: FieldExt>: Circuit<F> {
+    /// Returns the columns `config` was given to [`ConstraintSystem::enable_constant`],
+    /// in the order they were enabled.
+    fn constant_columns(config: &Self::Config) -> Vec<Column<Fixed>>;
+}
+
+impl<F: FieldExt, const DEPTH: usize> ConstantColumns<F> for SemaphoreCircuit<F, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    fn constant_columns(config: &Config<F>) -> Vec<Column<Fixed>> {
+        vec![config.constant]
+    }
+}
+
+impl<F: FieldExt, const N: usize, const DEPTH: usize> ConstantColumns<F>
+    for MultiGroupCircuit<F, N, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    fn constant_columns(config: &Config<F>) -> Vec<Column<Fixed>> {
+        vec![config.constant]
+    }
+}
+
+/// Returns the number of rows the circuit assigns, including any constants placed by
+/// its floor planner but not the blinding rows added by the prover.
+///
+/// The circuit is synthesized as given, so it should carry its witnesses if its
+/// synthesis depends on them.
+pub fn used_rows<F: FieldExt, C: ConstantColumns<F>>(circuit: &C) -> usize {
+    let mut cs = ConstraintSystem::default();
+    let config = C::configure(&mut cs);
+    let constants = C::constant_columns(&config);
+    let mut counter = RowCounter::default();
+    C::FloorPlanner::synthesize(&mut counter, circuit, config, constants)
+        .expect("circuit synthesis failed");
+    counter.rows
+}
+
+/// Returns the smallest `k` for which a circuit using `rows` rows fits in `2^k` rows,
+/// once the blinding rows the prover reserves are accounted for.
+pub fn minimum_k<F: FieldExt>(cs: &ConstraintSystem<F>, rows: usize) -> u32 {
+    let rows = (rows + cs.blinding_factors() + 1).max(cs.minimum_rows());
+    rows.next_power_of_two().trailing_zeros()
+}
+
+/// The size and cost of [`SemaphoreCircuit`] over a group tree of a given depth.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CircuitStats {
+    /// The depth of the group tree.
+    pub depth: usize,
+    pub advice_columns: usize,
+    pub fixed_columns: usize,
+    pub instance_columns: usize,
+    /// Selectors, which the prover may combine into additional fixed columns.
+    pub selectors: usize,
+    /// The degree of the constraint system, including the permutation argument.
+    pub max_degree: usize,
+    /// Rows assigned by the circuit, as counted by [`used_rows`].
+    pub rows: usize,
+    /// The smallest `k` the circuit can be proven with.
+    pub k: u32,
+    /// The size in bytes of a proof at that `k`, from halo2's cost model.
+    pub proof_size: usize,
+}
+
+/// Measures [`SemaphoreCircuit`] over `pallas::Base` for a group tree of depth `DEPTH`.
+///
+/// The circuit is synthesized without witnesses, so this is cheap even for deep trees.
+pub fn circuit_stats<const DEPTH: usize>() -> CircuitStats {
+    let circuit = SemaphoreCircuit::<Fp, DEPTH>::default();
+
+    let mut cs = ConstraintSystem::default();
+    SemaphoreCircuit::<Fp, DEPTH>::configure(&mut cs);
+    let rows = used_rows(&circuit);
+    let k = minimum_k(&cs, rows);
+
+    let cost = CircuitCost::<vesta::Point, _>::measure(k as usize, &circuit);
+
+    CircuitStats {
+        depth: DEPTH,
+        advice_columns: count_allocated(&cs, |cs| cs.advice_column()),
+        fixed_columns: count_allocated(&cs, |cs| cs.fixed_column()),
+        instance_columns: count_allocated(&cs, |cs| cs.instance_column()),
+        selectors: count_allocated(&cs, |cs| cs.selector()),
+        max_degree: cs.degree(),
+        rows,
+        k,
+        proof_size: cost.proof_size(1).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{dev::MockProver, pasta::Fp, plonk::ConstraintSystem};
+
+    use super::{circuit_stats, count_allocated, used_rows};
+    use crate::{
+        primitives::merkle::MerkleTree, witness::identity_commitment, SemaphoreCircuit,
+        MERKLE_DEPTH,
+    };
+
+    #[test]
+    fn semaphore_rows() {
+        let (trapdoor, nullifier) = (Fp::from(2), Fp::from(3));
+        let commitment = identity_commitment(trapdoor, nullifier);
+        let tree = MerkleTree::from_leaves(MERKLE_DEPTH, &[commitment]);
+        let (circuit, _): (SemaphoreCircuit<Fp>, _) =
+            SemaphoreCircuit::from_tree(trapdoor, nullifier, b"topic", &tree, 0);
+
+        // 263 rows before the Merkle swap was merged into the layer's hash region, and
+        // 151 with Poseidon2, which computes four partial rounds per row.
+        let rows = if cfg!(feature = "poseidon2") { 151 } else { 235 };
+        assert_eq!(used_rows(&circuit), rows);
+        assert_eq!(circuit_stats::<MERKLE_DEPTH>().rows, rows);
+    }
+
+    #[test]
+    fn minimum_k_fits() {
+        let (trapdoor, nullifier) = (Fp::from(2), Fp::from(3));
+        let commitment = identity_commitment(trapdoor, nullifier);
+        let tree = MerkleTree::from_leaves(MERKLE_DEPTH, &[commitment]);
+        let (circuit, public_inputs): (SemaphoreCircuit<Fp>, _) =
+            SemaphoreCircuit::from_tree(trapdoor, nullifier, b"topic", &tree, 0);
+        let instance = vec![public_inputs.to_instance()];
+
+        let k = circuit_stats::<MERKLE_DEPTH>().k;
+        let prover = MockProver::run(k, &circuit, instance.clone()).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // The circuit no longer fits once the blinding rows are reserved.
+        assert!(MockProver::run(k - 1, &circuit, instance).is_err());
+    }
+
+    #[test]
+    fn stats_grow_with_depth() {
+        let shallow = circuit_stats::<4>();
+        let deep = circuit_stats::<20>();
+
+        assert_eq!(deep.advice_columns, shallow.advice_columns);
+        assert_eq!(deep.selectors, shallow.selectors);
+        assert!(deep.rows > shallow.rows);
+        assert!(deep.k >= shallow.k);
+    }
+
+    #[test]
+    fn constraint_system_counts() {
+        let mut cs = ConstraintSystem::<Fp>::default();
+        for _ in 0..3 {
+            cs.advice_column();
+        }
+        cs.fixed_column();
+        cs.fixed_column();
+        cs.instance_column();
+        cs.lookup_table_column();
+        cs.selector();
+        cs.complex_selector();
+
+        assert_eq!(count_allocated(&cs, |cs| cs.advice_column()), 3);
+        // The lookup table column is a fixed column.
+        assert_eq!(count_allocated(&cs, |cs| cs.fixed_column()), 3);
+        assert_eq!(count_allocated(&cs, |cs| cs.instance_column()), 1);
+        assert_eq!(count_allocated(&cs, |cs| cs.selector()), 2);
+    }
+}
@@ -0,0 +1,77 @@
+//! Rendering of circuit layouts and namespace graphs, for debugging chip layouts, with
+//! halo2's `dev-graph` renderers.
+
+use std::{fs, io, path::Path};
+
+use halo2::{
+    dev::{circuit_dot_graph, CircuitLayout},
+    pasta::Fp,
+    plonk::Circuit,
+};
+use plotters::{coord::Shift, prelude::*};
+
+use super::circuit_stats;
+use crate::SemaphoreCircuit;
+
+fn drawing_error<E: std::error::Error + Send + Sync>(err: DrawingAreaErrorKind<E>) -> io::Error {
+    io::Error::other(err.to_string())
+}
+
+/// Renders the layout of `circuit` in `2^k` rows on `drawing_area`, with region labels
+/// and equality-constrained cells marked.
+fn render_layout<C: Circuit<Fp>, DB: DrawingBackend>(
+    k: u32,
+    circuit: &C,
+    drawing_area: &DrawingArea<DB, Shift>,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    drawing_area.fill(&WHITE)?;
+    CircuitLayout::default()
+        .mark_equality_cells(true)
+        .render(k as usize, circuit, drawing_area)?;
+    drawing_area.present()
+}
+
+/// Writes the layout of [`SemaphoreCircuit`] over `pallas::Base` for a group tree of
+/// depth `DEPTH` to `layout.png` and `layout.svg`, and its namespace graph to
+/// `circuit.dot`, in `dir`.
+///
+/// The layout is drawn at the smallest `k` the circuit fits in.
+pub fn write_semaphore_graphs<const DEPTH: usize>(dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let circuit = SemaphoreCircuit::<Fp, DEPTH>::default();
+    let k = circuit_stats::<DEPTH>().k;
+    let size = (1024, 768);
+
+    let png = dir.join("layout.png");
+    render_layout(k, &circuit, &BitMapBackend::new(&png, size).into_drawing_area())
+        .map_err(drawing_error)?;
+
+    let svg = dir.join("layout.svg");
+    render_layout(k, &circuit, &SVGBackend::new(&svg, size).into_drawing_area())
+        .map_err(drawing_error)?;
+
+    fs::write(dir.join("circuit.dot"), circuit_dot_graph(&circuit))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::write_semaphore_graphs;
+    use crate::MERKLE_DEPTH;
+
+    #[test]
+    fn semaphore_graphs() {
+        let dir = std::env::temp_dir().join("halo2-semaphore-graphs");
+        write_semaphore_graphs::<MERKLE_DEPTH>(&dir).unwrap();
+        for file in ["layout.png", "layout.svg", "circuit.dot"] {
+            assert!(dir.join(file).metadata().unwrap().len() > 0);
+        }
+
+        let dot = fs::read_to_string(dir.join("circuit.dot")).unwrap();
+        for layer in 0..MERKLE_DEPTH {
+            assert!(dot.contains(&format!("hash l {}", layer)));
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Rate-limited signals: one signal per topic per epoch.
+//!
+//! The external nullifier of a signal in epoch `e` on a topic is `H(topic, e)`, where the
+//! topic is mapped to a field element with [`hash_to_field::external_nullifier`] and
+//! time is cut into epochs of a fixed length. [`EpochCircuit`] derives the external nullifier in-circuit
+//! from the topic and the epoch, which are both public, so a verifier checks the epoch
+//! against its own clock instead of trusting the prover to combine them.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Layouter, SimpleFloorPlanner},
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+
+use crate::{
+    gadget::{hash::HashInstructions, merkle::MerklePath},
+    primitives::{hash_to_field, merkle::MerkleTree, poseidon::CachedSpec, poseidon::Spec, HashSpec},
+    utils::{CellValue, UtilitiesInstructions},
+    witness::{hash, identity_commitment, merkle_witness, nullifier_hash, PublicInputs},
+    Config, SemaphoreCircuit, EXTERNAL_NULLIFIER, MERKLE_DEPTH, NULLIFIER_HASH, ROOT,
+};
+
+// Absolute offsets for the public inputs following those of `SemaphoreCircuit`.
+const TOPIC: usize = 3;
+const EPOCH: usize = 4;
+
+/// Returns the epoch containing `time`, for epochs of `length` starting at the Unix
+/// epoch.
+///
+/// # Panics
+///
+/// Panics if `time` is before the Unix epoch or if `length` is shorter than a second.
+pub fn epoch_at(time: SystemTime, length: Duration) -> u64 {
+    let elapsed = time.duration_since(UNIX_EPOCH).expect("time is before the Unix epoch");
+    elapsed.as_secs() / length.as_secs()
+}
+
+/// Returns the external nullifier for signals on `topic` during `epoch`.
+pub fn epoch_external_nullifier<F: FieldExt>(topic: &[u8], epoch: u64) -> F
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    hash([hash_to_field::external_nullifier(topic), F::from_u64(epoch)])
+}
+
+/// A Semaphore signal on a topic during an epoch.
+///
+/// Its instance column holds the public inputs of [`SemaphoreCircuit`], whose external
+/// nullifier is constrained to `H(topic, epoch)`, followed by the topic and the epoch.
+#[derive(Clone, Debug, Default)]
+pub struct EpochCircuit<F: FieldExt, const DEPTH: usize = MERKLE_DEPTH> {
+    pub identity_trapdoor: Option<F>,
+    pub identity_nullifier: Option<F>,
+    pub topic: Option<F>,
+    pub epoch: Option<F>,
+    pub position_bits: Option<[F; DEPTH]>,
+    pub path: Option<[F; DEPTH]>,
+}
+
+impl<F: FieldExt, const DEPTH: usize> UtilitiesInstructions<F> for EpochCircuit<F, DEPTH> {
+    type Var = CellValue<F>;
+}
+
+impl<F: FieldExt, const DEPTH: usize> Circuit<F> for EpochCircuit<F, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    type Config = Config<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        SemaphoreCircuit::<F, DEPTH>::configure(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let identity_trapdoor = self.load_private(
+            layouter.namespace(|| "witness identity_trapdoor"),
+            config.advices[0],
+            self.identity_trapdoor,
+        )?;
+        let identity_nullifier = self.load_private(
+            layouter.namespace(|| "witness identity_nullifier"),
+            config.advices[0],
+            self.identity_nullifier,
+        )?;
+        let topic = self.load_private(layouter.namespace(|| "witness topic"), config.advices[0], self.topic)?;
+        let epoch = self.load_private(layouter.namespace(|| "witness epoch"), config.advices[0], self.epoch)?;
+
+        let poseidon_chip = config.construct_poseidon_chip();
+        let identity_commitment = poseidon_chip.hash(
+            layouter.namespace(|| "hash to identity commitment"),
+            [identity_trapdoor, identity_nullifier],
+        )?;
+        let external_nullifier = poseidon_chip.hash(
+            layouter.namespace(|| "hash to external nullifier"),
+            [topic, epoch],
+        )?;
+        let nullifier_hash = poseidon_chip.hash(
+            layouter.namespace(|| "hash to nullifier hash"),
+            [identity_nullifier, external_nullifier],
+        )?;
+
+        let merkle_inputs = MerklePath {
+            chip: config.construct_merkle_chip(),
+            leaf_pos: self.position_bits,
+            path: self.path,
+        };
+        let root = merkle_inputs.calculate_root(
+            layouter.namespace(|| "merkle root calculation"),
+            identity_commitment,
+        )?;
+
+        self.expose_public(layouter.namespace(|| "constrain external_nullifier"), config.instance, external_nullifier, EXTERNAL_NULLIFIER)?;
+        self.expose_public(layouter.namespace(|| "constrain nullifier_hash"), config.instance, nullifier_hash, NULLIFIER_HASH)?;
+        self.expose_public(layouter.namespace(|| "constrain root"), config.instance, root, ROOT)?;
+        self.expose_public(layouter.namespace(|| "constrain topic"), config.instance, topic, TOPIC)?;
+        self.expose_public(layouter.namespace(|| "constrain epoch"), config.instance, epoch, EPOCH)
+    }
+}
+
+/// The public inputs of [`EpochCircuit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EpochPublicInputs<F: FieldExt> {
+    pub semaphore: PublicInputs<F>,
+    pub topic: F,
+    pub epoch: u64,
+}
+
+impl<F: FieldExt> EpochPublicInputs<F> {
+    /// Returns the instance column expected by [`EpochCircuit`].
+    pub fn to_instance(&self) -> Vec<F> {
+        let mut instance = self.semaphore.to_instance();
+        instance.push(self.topic);
+        instance.push(F::from_u64(self.epoch));
+        instance
+    }
+
+    /// Returns whether the signal was made for the epoch containing `now`, or the one
+    /// before it, which leaves a signal made at the end of an epoch time to arrive.
+    pub fn is_current(&self, now: SystemTime, length: Duration) -> bool {
+        let current = epoch_at(now, length);
+        self.epoch == current || self.epoch + 1 == current
+    }
+}
+
+impl<F: FieldExt, const DEPTH: usize> EpochCircuit<F, DEPTH>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Builds the witness proving that the identity at `index` in `tree` signals on
+    /// `topic` during `epoch`, together with the matching public inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics under the conditions of [`SemaphoreCircuit::from_tree`].
+    pub fn from_tree(
+        identity_trapdoor: F,
+        identity_nullifier: F,
+        topic: &[u8],
+        epoch: u64,
+        tree: &MerkleTree<F>,
+        index: usize,
+    ) -> (Self, EpochPublicInputs<F>) {
+        let commitment = identity_commitment(identity_trapdoor, identity_nullifier);
+        let (path, position_bits) = merkle_witness(commitment, tree, index);
+
+        let external_nullifier = epoch_external_nullifier(topic, epoch);
+        let public_inputs = EpochPublicInputs {
+            semaphore: PublicInputs {
+                external_nullifier,
+                nullifier_hash: nullifier_hash(identity_nullifier, external_nullifier),
+                root: tree.root(),
+            },
+            topic: hash_to_field::external_nullifier(topic),
+            epoch,
+        };
+        let circuit = EpochCircuit {
+            identity_trapdoor: Some(identity_trapdoor),
+            identity_nullifier: Some(identity_nullifier),
+            topic: Some(public_inputs.topic),
+            epoch: Some(F::from_u64(epoch)),
+            position_bits: Some(position_bits),
+            path: Some(path),
+        };
+
+        (circuit, public_inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use halo2::{dev::MockProver, pasta::Fp};
+
+    use super::{epoch_at, epoch_external_nullifier, EpochCircuit};
+    use crate::{primitives::merkle::MerkleTree, witness::identity_commitment, MERKLE_DEPTH};
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[test]
+    fn external_nullifier_is_derived_in_circuit() {
+        let (trapdoor, nullifier) = (Fp::from(2), Fp::from(3));
+        let tree = MerkleTree::from_leaves(MERKLE_DEPTH, &[identity_commitment(trapdoor, nullifier)]);
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let epoch = epoch_at(now, DAY);
+        let (circuit, public_inputs) =
+            EpochCircuit::<Fp>::from_tree(trapdoor, nullifier, b"posts", epoch, &tree, 0);
+        assert!(public_inputs.is_current(now, DAY));
+        assert!(public_inputs.is_current(now + DAY, DAY));
+        assert!(!public_inputs.is_current(now + 2 * DAY, DAY));
+
+        let prover = MockProver::run(10, &circuit, vec![public_inputs.to_instance()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // The next epoch has a fresh nullifier hash.
+        let (_, tomorrow) =
+            EpochCircuit::<Fp>::from_tree(trapdoor, nullifier, b"posts", epoch + 1, &tree, 0);
+        assert_ne!(tomorrow.semaphore.nullifier_hash, public_inputs.semaphore.nullifier_hash);
+
+        // The proof does not hold for another epoch, even with its external nullifier.
+        let mut other = public_inputs;
+        other.epoch = epoch + 1;
+        let prover = MockProver::run(10, &circuit, vec![other.to_instance()]).unwrap();
+        assert!(prover.verify().is_err());
+        other.semaphore.external_nullifier = epoch_external_nullifier(b"posts", epoch + 1);
+        let prover = MockProver::run(10, &circuit, vec![other.to_instance()]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
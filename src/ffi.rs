@@ -0,0 +1,361 @@
+//! A C ABI for creating identities, proving and verifying.
+//!
+//! The functions work on byte buffers and return a [`SemaphoreStatus`]. Buffers
+//! returned through an out pointer are owned by the caller, who releases them with
+//! [`semaphore_buffer_free`]. The declarations are in `include/semaphore.h`.
+//!
+//! Field elements are 32 bytes, little endian. An identity is its trapdoor followed by
+//! its nullifier, and a proof is a serialized [`SemaphoreProof`]. Proofs are made for
+//! trees of depth [`MERKLE_DEPTH`], with keys generated on first use.
+
+use std::{ptr, slice};
+
+use halo2::{arithmetic::FieldExt, pasta::Fp};
+
+use crate::{
+    primitives::merkle::{empty_leaf, MerkleTree},
+    proof::{default_keys, prove, verify, ProofError, SemaphoreProof},
+    witness::{identity_commitment, identity_from_secret},
+    SemaphoreCircuit, MERKLE_DEPTH,
+};
+
+/// The result of a call through the C ABI.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SemaphoreStatus {
+    Ok = 0,
+    /// A required pointer is null.
+    NullPointer = 1,
+    /// An input buffer has the wrong length or holds a non-canonical field element.
+    InvalidInput = 2,
+    /// The identity is not the member at the given index of the group.
+    NotMember = 3,
+    /// The prover failed.
+    ProverError = 4,
+    /// The proof is not a serialized proof of a known version.
+    MalformedProof = 5,
+    /// The proof was made for another circuit.
+    CircuitMismatch = 6,
+    /// The proof does not verify.
+    InvalidProof = 7,
+}
+
+impl From<ProofError> for SemaphoreStatus {
+    fn from(error: ProofError) -> Self {
+        match error {
+            ProofError::Malformed => SemaphoreStatus::MalformedProof,
+            ProofError::CircuitMismatch { .. } => SemaphoreStatus::CircuitMismatch,
+            ProofError::Invalid => SemaphoreStatus::InvalidProof,
+        }
+    }
+}
+
+/// A byte buffer allocated by this library.
+#[repr(C)]
+#[derive(Debug)]
+pub struct SemaphoreBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl SemaphoreBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        SemaphoreBuffer { data, len }
+    }
+}
+
+/// Returns the `len` bytes at `data`, which may only be null when `len` is zero.
+unsafe fn input<'a>(data: *const u8, len: usize) -> Result<&'a [u8], SemaphoreStatus> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(SemaphoreStatus::NullPointer)
+    } else {
+        Ok(slice::from_raw_parts(data, len))
+    }
+}
+
+fn field_elements(bytes: &[u8]) -> Result<Vec<Fp>, SemaphoreStatus> {
+    if !bytes.len().is_multiple_of(32) {
+        return Err(SemaphoreStatus::InvalidInput);
+    }
+    bytes
+        .chunks(32)
+        .map(|chunk| Option::from(Fp::from_bytes(chunk.try_into().unwrap())).ok_or(SemaphoreStatus::InvalidInput))
+        .collect()
+}
+
+fn identity(bytes: &[u8]) -> Result<(Fp, Fp), SemaphoreStatus> {
+    match field_elements(bytes)?[..] {
+        [trapdoor, nullifier] => Ok((trapdoor, nullifier)),
+        _ => Err(SemaphoreStatus::InvalidInput),
+    }
+}
+
+unsafe fn write(out: *mut SemaphoreBuffer, bytes: Vec<u8>) -> SemaphoreStatus {
+    *out = SemaphoreBuffer::new(bytes);
+    SemaphoreStatus::Ok
+}
+
+/// Derives an identity from `secret`, and writes its 64 bytes to `out`.
+///
+/// # Safety
+///
+/// `secret` must point to `secret_len` readable bytes, and `out` to a writable buffer.
+#[no_mangle]
+pub unsafe extern "C" fn semaphore_identity_new(
+    secret: *const u8,
+    secret_len: usize,
+    out: *mut SemaphoreBuffer,
+) -> SemaphoreStatus {
+    if out.is_null() {
+        return SemaphoreStatus::NullPointer;
+    }
+    let secret = match input(secret, secret_len) {
+        Ok(secret) => secret,
+        Err(status) => return status,
+    };
+
+    let (trapdoor, nullifier): (Fp, Fp) = identity_from_secret(secret);
+    write(out, [trapdoor.to_bytes(), nullifier.to_bytes()].concat())
+}
+
+/// Writes the 32-byte identity commitment of the 64-byte `identity` to `out`.
+///
+/// # Safety
+///
+/// `identity` must point to `identity_len` readable bytes, and `out` to a writable
+/// buffer.
+#[no_mangle]
+pub unsafe extern "C" fn semaphore_commitment(
+    identity: *const u8,
+    identity_len: usize,
+    out: *mut SemaphoreBuffer,
+) -> SemaphoreStatus {
+    if out.is_null() {
+        return SemaphoreStatus::NullPointer;
+    }
+    let (trapdoor, nullifier) = match input(identity, identity_len).and_then(self::identity) {
+        Ok(identity) => identity,
+        Err(status) => return status,
+    };
+
+    write(out, identity_commitment(trapdoor, nullifier).to_bytes().to_vec())
+}
+
+/// Proves that `identity` is the member at `index` of the group whose commitments are
+/// `leaves`, 32 bytes each, and signals on `topic`. Writes the serialized proof to `out`.
+///
+/// The leaves of removed members are zero, the empty leaf, and no identity can prove
+/// at their index.
+///
+/// # Safety
+///
+/// Each input must point to as many readable bytes as its length, and `out` to a
+/// writable buffer.
+#[no_mangle]
+pub unsafe extern "C" fn semaphore_prove(
+    identity: *const u8,
+    identity_len: usize,
+    leaves: *const u8,
+    leaves_len: usize,
+    index: usize,
+    topic: *const u8,
+    topic_len: usize,
+    out: *mut SemaphoreBuffer,
+) -> SemaphoreStatus {
+    if out.is_null() {
+        return SemaphoreStatus::NullPointer;
+    }
+    let inputs = input(identity, identity_len)
+        .and_then(self::identity)
+        .and_then(|identity| Ok((identity, field_elements(input(leaves, leaves_len)?)?)))
+        .and_then(|inputs| Ok((inputs, input(topic, topic_len)?)));
+    let (((trapdoor, nullifier), leaves), topic) = match inputs {
+        Ok(inputs) => inputs,
+        Err(status) => return status,
+    };
+
+    if leaves.len() > 1 << MERKLE_DEPTH {
+        return SemaphoreStatus::InvalidInput;
+    }
+    match leaves.get(index) {
+        Some(&leaf) if leaf != empty_leaf() && leaf == identity_commitment(trapdoor, nullifier) => {}
+        _ => return SemaphoreStatus::NotMember,
+    }
+    let tree = MerkleTree::from_leaves(MERKLE_DEPTH, &leaves);
+    let (circuit, public_inputs) =
+        SemaphoreCircuit::<Fp, MERKLE_DEPTH>::from_tree(trapdoor, nullifier, topic, &tree, index);
+
+    let (params, pk) = default_keys();
+    match prove(params, pk, circuit, public_inputs) {
+        Ok(proof) => write(out, proof.to_bytes()),
+        Err(_) => SemaphoreStatus::ProverError,
+    }
+}
+
+/// Verifies a serialized proof.
+///
+/// # Safety
+///
+/// `proof` must point to `proof_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn semaphore_verify(proof: *const u8, proof_len: usize) -> SemaphoreStatus {
+    let proof = match input(proof, proof_len) {
+        Ok(proof) => proof,
+        Err(status) => return status,
+    };
+
+    let (params, pk) = default_keys();
+    match SemaphoreProof::from_bytes(proof).and_then(|proof| verify::<MERKLE_DEPTH>(params, pk.get_vk(), &proof)) {
+        Ok(()) => SemaphoreStatus::Ok,
+        Err(error) => error.into(),
+    }
+}
+
+/// Releases a buffer returned by this library, and resets it to empty.
+///
+/// # Safety
+///
+/// `buffer` must be null, or point to a buffer returned by this library that has not
+/// been released.
+#[no_mangle]
+pub unsafe extern "C" fn semaphore_buffer_free(buffer: *mut SemaphoreBuffer) {
+    if buffer.is_null() || (*buffer).data.is_null() {
+        return;
+    }
+    let SemaphoreBuffer { data, len } = *buffer;
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+    *buffer = SemaphoreBuffer {
+        data: ptr::null_mut(),
+        len: 0,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        semaphore_buffer_free, semaphore_commitment, semaphore_identity_new, semaphore_prove,
+        semaphore_verify, SemaphoreBuffer, SemaphoreStatus,
+    };
+
+    const HEADER: &str = include_str!("../include/semaphore.h");
+
+    /// The C spelling of a type used by the ABI.
+    trait CType {
+        const NAME: &'static str;
+    }
+
+    impl CType for *const u8 {
+        const NAME: &'static str = "const uint8_t *";
+    }
+
+    impl CType for *mut u8 {
+        const NAME: &'static str = "uint8_t *";
+    }
+
+    impl CType for usize {
+        const NAME: &'static str = "size_t ";
+    }
+
+    impl CType for *mut SemaphoreBuffer {
+        const NAME: &'static str = "SemaphoreBuffer *";
+    }
+
+    impl CType for SemaphoreStatus {
+        const NAME: &'static str = "SemaphoreStatus ";
+    }
+
+    impl CType for () {
+        const NAME: &'static str = "void ";
+    }
+
+    /// Checks that `$name` has the given signature, and returns its C prototype.
+    macro_rules! prototype {
+        ($name:ident($($arg:ident: $ty:ty),*) -> $ret:ty) => {{
+            let _: unsafe extern "C" fn($($ty),*) -> $ret = $name;
+            let args: Vec<String> = vec![$(format!("{}{}", <$ty as CType>::NAME, stringify!($arg))),*];
+            format!("{}{}({});", <$ret as CType>::NAME, stringify!($name), args.join(", "))
+        }};
+    }
+
+    fn c_name(status: SemaphoreStatus) -> &'static str {
+        match status {
+            SemaphoreStatus::Ok => "SEMAPHORE_OK",
+            SemaphoreStatus::NullPointer => "SEMAPHORE_NULL_POINTER",
+            SemaphoreStatus::InvalidInput => "SEMAPHORE_INVALID_INPUT",
+            SemaphoreStatus::NotMember => "SEMAPHORE_NOT_MEMBER",
+            SemaphoreStatus::ProverError => "SEMAPHORE_PROVER_ERROR",
+            SemaphoreStatus::MalformedProof => "SEMAPHORE_MALFORMED_PROOF",
+            SemaphoreStatus::CircuitMismatch => "SEMAPHORE_CIRCUIT_MISMATCH",
+            SemaphoreStatus::InvalidProof => "SEMAPHORE_INVALID_PROOF",
+        }
+    }
+
+    #[test]
+    fn header_matches_abi() {
+        // The header without comments, on one line with single spaces.
+        let mut code = String::new();
+        let mut rest = HEADER;
+        while let Some(start) = rest.find("/*") {
+            code.push_str(&rest[..start]);
+            rest = &rest[start + rest[start..].find("*/").unwrap() + 2..];
+        }
+        code.push_str(rest);
+        let header = code
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(str::split_whitespace)
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let statuses = [
+            SemaphoreStatus::Ok,
+            SemaphoreStatus::NullPointer,
+            SemaphoreStatus::InvalidInput,
+            SemaphoreStatus::NotMember,
+            SemaphoreStatus::ProverError,
+            SemaphoreStatus::MalformedProof,
+            SemaphoreStatus::CircuitMismatch,
+            SemaphoreStatus::InvalidProof,
+        ];
+        let variants: Vec<_> = statuses
+            .iter()
+            .map(|&status| format!("{} = {},", c_name(status), status as i32))
+            .collect();
+        let status = format!("typedef enum SemaphoreStatus {{ {} }} SemaphoreStatus;", variants.join(" "));
+        assert!(header.contains(&status), "the header does not declare {}", status);
+
+        let _: fn(SemaphoreBuffer) -> (*mut u8, usize) = |buffer| (buffer.data, buffer.len);
+        let buffer = format!(
+            "typedef struct SemaphoreBuffer {{ {}data; {}len; }} SemaphoreBuffer;",
+            <*mut u8 as CType>::NAME,
+            <usize as CType>::NAME,
+        );
+        assert!(header.contains(&buffer), "the header does not declare {}", buffer);
+
+        let prototypes = [
+            prototype!(semaphore_identity_new(secret: *const u8, secret_len: usize, out: *mut SemaphoreBuffer) -> SemaphoreStatus),
+            prototype!(semaphore_commitment(identity: *const u8, identity_len: usize, out: *mut SemaphoreBuffer) -> SemaphoreStatus),
+            prototype!(semaphore_prove(
+                identity: *const u8,
+                identity_len: usize,
+                leaves: *const u8,
+                leaves_len: usize,
+                index: usize,
+                topic: *const u8,
+                topic_len: usize,
+                out: *mut SemaphoreBuffer
+            ) -> SemaphoreStatus),
+            prototype!(semaphore_verify(proof: *const u8, proof_len: usize) -> SemaphoreStatus),
+            prototype!(semaphore_buffer_free(buffer: *mut SemaphoreBuffer) -> ()),
+        ];
+        for prototype in &prototypes {
+            assert!(header.contains(prototype), "the header does not declare {}", prototype);
+        }
+        // Every function the header declares is checked above.
+        assert_eq!(header.matches("(").count(), prototypes.len());
+    }
+}
@@ -0,0 +1,168 @@
+//! Deterministic identities, groups and proofs for tests, benches and examples.
+//!
+//! Everything is derived from a seed with ChaCha20, so the same seed gives the same
+//! identities, group and public inputs on every machine. Proofs are not reproducible
+//! byte for byte, as the prover draws its blinding factors from the operating system.
+//!
+//! The golden files under `fixtures/golden` pin the verifying key fingerprint, the public
+//! inputs and a proof of [`Fixture::new`]`(`[`SEED`]`)`. A change to the circuit or the
+//! hash constants changes the fingerprint and makes the golden proof fail to verify. Run
+//! the tests with `UPDATE_GOLDEN=1` to rewrite the files after an intended change.
+
+use halo2::{
+    arithmetic::FieldExt,
+    pasta::{EqAffine, Fp},
+    plonk::{create_proof, Error, ProvingKey},
+    poly::commitment::Params,
+    transcript::{Blake2bWrite, Challenge255},
+};
+use rand_chacha::ChaCha20Rng;
+use rand_core::{RngCore, SeedableRng};
+
+use crate::{
+    group::Group,
+    primitives::{poseidon::CachedSpec, HashSpec},
+    witness::{identity_commitment, PublicInputs},
+    SemaphoreCircuit, MERKLE_DEPTH,
+};
+
+/// The seed of the golden fixture.
+pub const SEED: u64 = 0x5e4a_9407e;
+
+/// The number of members in a fixture group.
+pub const MEMBERS: usize = 5;
+
+/// The topic of a fixture signal.
+pub const TOPIC: &[u8] = b"https://example.com/poll/1";
+
+/// Returns the random number generator for `seed`.
+pub fn rng(seed: u64) -> ChaCha20Rng {
+    ChaCha20Rng::seed_from_u64(seed)
+}
+
+/// Returns a random identity trapdoor and nullifier.
+pub fn identity<F: FieldExt>(rng: &mut impl RngCore) -> (F, F) {
+    (F::random(&mut *rng), F::random(&mut *rng))
+}
+
+/// A group of random identities, and a signal on [`TOPIC`] from one of them.
+#[derive(Clone, Debug)]
+pub struct Fixture<F: FieldExt, const DEPTH: usize = MERKLE_DEPTH> {
+    pub identities: Vec<(F, F)>,
+    pub group: Group<F>,
+    pub signer: usize,
+    pub circuit: SemaphoreCircuit<F, DEPTH>,
+    pub public_inputs: PublicInputs<F>,
+}
+
+impl<F: FieldExt, const DEPTH: usize> Fixture<F, DEPTH>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Builds the fixture for `seed`, with [`MEMBERS`] members.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = rng(seed);
+
+        let identities: Vec<(F, F)> = (0..MEMBERS).map(|_| identity(&mut rng)).collect();
+        let mut group = Group::new(DEPTH);
+        for (trapdoor, nullifier) in &identities {
+            group.add(identity_commitment(*trapdoor, *nullifier)).unwrap();
+        }
+
+        let signer = (rng.next_u64() % MEMBERS as u64) as usize;
+        let (trapdoor, nullifier) = identities[signer];
+        let (circuit, public_inputs) =
+            SemaphoreCircuit::from_group(trapdoor, nullifier, TOPIC, &group, signer).unwrap();
+
+        Fixture {
+            identities,
+            group,
+            signer,
+            circuit,
+            public_inputs,
+        }
+    }
+}
+
+/// Proves the fixture's signal.
+pub fn prove<const DEPTH: usize>(
+    params: &Params<EqAffine>,
+    pk: &ProvingKey<EqAffine>,
+    fixture: &Fixture<Fp, DEPTH>,
+) -> Result<Vec<u8>, Error> {
+    let instance = fixture.public_inputs.to_instance();
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    create_proof(params, pk, std::slice::from_ref(&fixture.circuit), &[&[&instance]], &mut transcript)?;
+    Ok(transcript.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::PathBuf};
+
+    use halo2::{
+        arithmetic::FieldExt,
+        pasta::Fp,
+        plonk::verify_proof,
+        transcript::{Blake2bRead, Challenge255},
+    };
+
+    use super::{prove, Fixture, SEED};
+    use crate::{
+        proof::{from_hex, keygen, to_hex, vk_fingerprint},
+        MERKLE_DEPTH,
+    };
+
+    fn golden_dir() -> PathBuf {
+        let hash = if cfg!(feature = "poseidon2") { "poseidon2" } else { "poseidon" };
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/golden").join(hash)
+    }
+
+    #[test]
+    fn fixtures_are_deterministic() {
+        let fixture = Fixture::<Fp>::new(SEED);
+        let again = Fixture::<Fp>::new(SEED);
+        assert_eq!(fixture.identities, again.identities);
+        assert_eq!(fixture.public_inputs, again.public_inputs);
+        assert_ne!(Fixture::<Fp>::new(SEED + 1).public_inputs, fixture.public_inputs);
+    }
+
+    #[test]
+    fn golden() {
+        let fixture = Fixture::<Fp, MERKLE_DEPTH>::new(SEED);
+        let (params, pk) = keygen::<MERKLE_DEPTH>().unwrap();
+
+        let fingerprint = to_hex(&vk_fingerprint(pk.get_vk()));
+        let public_inputs: String = fixture
+            .public_inputs
+            .to_instance()
+            .iter()
+            .map(|value| to_hex(&value.to_bytes()) + "\n")
+            .collect();
+
+        let dir = golden_dir();
+        if env::var_os("UPDATE_GOLDEN").is_some() {
+            let proof = prove(&params, &pk, &fixture).unwrap();
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("vk_fingerprint"), fingerprint + "\n").unwrap();
+            fs::write(dir.join("public_inputs"), public_inputs).unwrap();
+            fs::write(dir.join("proof"), to_hex(&proof) + "\n").unwrap();
+            return;
+        }
+
+        let read = |name: &str| {
+            fs::read_to_string(dir.join(name))
+                .unwrap_or_else(|_| panic!("missing golden file {}; run with UPDATE_GOLDEN=1", name))
+        };
+        assert_eq!(read("vk_fingerprint").trim(), fingerprint, "the verifying key changed");
+        assert_eq!(read("public_inputs"), public_inputs, "the fixture's public inputs changed");
+
+        // The golden proof still verifies against the current circuit.
+        let proof = from_hex(read("proof").trim()).expect("the golden proof is not hex");
+        let instance = fixture.public_inputs.to_instance();
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let guard = verify_proof(&params, pk.get_vk(), params.empty_msm(), &[&[&instance]], &mut transcript)
+            .expect("the golden proof is malformed");
+        assert!(guard.use_challenges().eval(), "the golden proof no longer verifies");
+    }
+}
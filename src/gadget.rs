@@ -1,22 +1,39 @@
-use halo2::{
-    pasta::Fp
-};
+use halo2::arithmetic::FieldExt;
 
+pub mod comparison;
+pub mod decompose;
+pub mod hash;
+pub mod is_zero;
 pub mod merkle;
 pub mod poseidon;
+pub mod range_check;
 
 
-use crate::gadget::{
-    merkle::*,
-    poseidon::{Pow5T3Chip as PoseidonChip}
-};
+use crate::gadget::merkle::*;
+use crate::primitives::{poseidon::Spec, HashSpec};
 
-impl super::Config {
-    pub(super) fn construct_merkle_chip(&self) -> MerkleChip {
+/// The chip for [`HashSpec`](crate::primitives::HashSpec).
+#[cfg(not(feature = "poseidon2"))]
+pub type HashChip<F> = poseidon::Pow5T3Chip<F>;
+/// The config of [`HashChip`].
+#[cfg(not(feature = "poseidon2"))]
+pub type HashConfig<F> = poseidon::Pow5T3Config<F>;
+/// The chip for [`HashSpec`](crate::primitives::HashSpec).
+#[cfg(feature = "poseidon2")]
+pub type HashChip<F> = poseidon::Poseidon2Chip<F>;
+/// The config of [`HashChip`].
+#[cfg(feature = "poseidon2")]
+pub type HashConfig<F> = poseidon::Poseidon2Config<F>;
+
+impl<F: FieldExt> super::Config<F>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    pub(super) fn construct_merkle_chip(&self) -> MerkleChip<F, HashChip<F>> {
         MerkleChip::construct(self.merkle_config.clone())
     }
 
-    pub(super) fn construct_poseidon_chip(&self) -> PoseidonChip<Fp> {
-        PoseidonChip::construct(self.poseidon_config.clone())
+    pub(super) fn construct_poseidon_chip(&self) -> HashChip<F> {
+        HashChip::construct(self.poseidon_config.clone())
     }
 }
\ No newline at end of file
@@ -0,0 +1,276 @@
+use std::marker::PhantomData;
+
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use super::range_check::RangeCheckInstructions;
+use crate::utils::{copy, CellValue, Var};
+
+pub trait ComparisonInstructions<F: FieldExt>: Chip<F> {
+    type Cell;
+
+    /// Returns a cell holding one if `a < b`, and zero otherwise.
+    ///
+    /// Both values must already be known to fit in the chip's number of bits.
+    fn less_than(
+        &self,
+        layouter: impl Layouter<F>,
+        a: Self::Cell,
+        b: Self::Cell,
+    ) -> Result<Self::Cell, Error>;
+}
+
+/// Configuration for a [`LessThanChip`], with the configuration `C` of its range check.
+#[derive(Clone, Debug)]
+pub struct LessThanConfig<C> {
+    /// The columns holding `a`, `b`, the result and the remainder.
+    pub advice: [Column<Advice>; 4],
+    pub s_less_than: Selector,
+    pub range_check: C,
+}
+
+/// A chip comparing values of `N` bits, where `N` is the number of bits checked by the
+/// range check chip `R`.
+///
+/// The prover witnesses the boolean result `lt` and the remainder
+/// `r = a - b + lt * 2^N`, which is range checked to `N` bits with `R`, either a
+/// [`RangeCheckChip`] or a [`LookupRangeCheckChip`]. Only the correct result leaves `r`
+/// in range, as long as `a` and `b` are themselves below `2^N`.
+///
+/// [`RangeCheckChip`]: super::range_check::RangeCheckChip
+/// [`LookupRangeCheckChip`]: super::range_check::LookupRangeCheckChip
+#[derive(Clone, Debug)]
+pub struct LessThanChip<F: FieldExt, R: RangeCheckInstructions<F>> {
+    config: LessThanConfig<R::Config>,
+    _marker: PhantomData<(F, R)>,
+}
+
+impl<F: FieldExt, R: RangeCheckInstructions<F>> Chip<F> for LessThanChip<F, R> {
+    type Config = LessThanConfig<R::Config>;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt, R: RangeCheckInstructions<F>> LessThanChip<F, R> {
+    fn shift() -> F {
+        F::from_u64(2).pow_vartime([R::NUM_BITS as u64])
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 4],
+        range_check: R::Config,
+    ) -> LessThanConfig<R::Config> {
+        for column in &advice {
+            meta.enable_equality((*column).into());
+        }
+
+        let s_less_than = meta.selector();
+
+        meta.create_gate("less than", |meta| {
+            let s_less_than = meta.query_selector(s_less_than);
+            let a = meta.query_advice(advice[0], Rotation::cur());
+            let b = meta.query_advice(advice[1], Rotation::cur());
+            let lt = meta.query_advice(advice[2], Rotation::cur());
+            let r = meta.query_advice(advice[3], Rotation::cur());
+            vec![
+                s_less_than.clone() * lt.clone() * (Expression::Constant(F::one()) - lt.clone()),
+                s_less_than * (a - b + lt * Self::shift() - r),
+            ]
+        });
+
+        LessThanConfig {
+            advice,
+            s_less_than,
+            range_check,
+        }
+    }
+
+    pub fn construct(config: LessThanConfig<R::Config>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt, R: RangeCheckInstructions<F, Cell = CellValue<F>>> ComparisonInstructions<F>
+    for LessThanChip<F, R>
+{
+    type Cell = CellValue<F>;
+
+    fn less_than(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: Self::Cell,
+        b: Self::Cell,
+    ) -> Result<Self::Cell, Error> {
+        let config = self.config.clone();
+
+        let (lt, r) = layouter.assign_region(
+            || "less than",
+            |mut region| {
+                config.s_less_than.enable(&mut region, 0)?;
+                copy(&mut region, || "a", config.advice[0], 0, &a)?;
+                copy(&mut region, || "b", config.advice[1], 0, &b)?;
+
+                let lt = a.value().zip(b.value()).map(|(a, b)| F::from(a < b));
+                let lt_cell = region.assign_advice(
+                    || "lt",
+                    config.advice[2],
+                    0,
+                    || lt.ok_or(Error::SynthesisError),
+                )?;
+
+                let r = a
+                    .value()
+                    .zip(b.value())
+                    .zip(lt)
+                    .map(|((a, b), lt)| a - b + lt * Self::shift());
+                let r_cell = region.assign_advice(
+                    || "r",
+                    config.advice[3],
+                    0,
+                    || r.ok_or(Error::SynthesisError),
+                )?;
+
+                Ok((CellValue::new(lt_cell, lt), CellValue::new(r_cell, r)))
+            },
+        )?;
+
+        R::construct(config.range_check)
+            .range_check(layouter.namespace(|| "range check remainder"), r)?;
+
+        Ok(lt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use halo2::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+
+    use super::{ComparisonInstructions, LessThanChip, LessThanConfig};
+    use crate::{
+        gadget::range_check::{LookupRangeCheckChip, RangeCheckChip, RangeCheckInstructions},
+        utils::{CellValue, UtilitiesInstructions},
+    };
+
+    /// A range check chip of 16 bits, with how to configure it and load its table.
+    trait TestRangeCheck: RangeCheckInstructions<Fp, Cell = CellValue<Fp>> {
+        fn configure(meta: &mut ConstraintSystem<Fp>, column: Column<Advice>) -> Self::Config;
+
+        fn load(&self, _layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    impl TestRangeCheck for RangeCheckChip<Fp, 16> {
+        fn configure(meta: &mut ConstraintSystem<Fp>, column: Column<Advice>) -> Self::Config {
+            RangeCheckChip::<Fp, 16>::configure(meta, column)
+        }
+    }
+
+    // Four words of four bits.
+    impl TestRangeCheck for LookupRangeCheckChip<Fp, 4, 4> {
+        fn configure(meta: &mut ConstraintSystem<Fp>, column: Column<Advice>) -> Self::Config {
+            let table = meta.lookup_table_column();
+            LookupRangeCheckChip::<Fp, 4, 4>::configure(meta, column, table)
+        }
+
+        fn load(&self, layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            self.load_table(layouter)
+        }
+    }
+
+    struct LessThanCircuit<R> {
+        a: Option<Fp>,
+        b: Option<Fp>,
+        _marker: PhantomData<R>,
+    }
+
+    impl<R> UtilitiesInstructions<Fp> for LessThanCircuit<R> {
+        type Var = CellValue<Fp>;
+    }
+
+    impl<R: TestRangeCheck> Circuit<Fp> for LessThanCircuit<R> {
+        type Config = (LessThanConfig<R::Config>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            LessThanCircuit {
+                a: None,
+                b: None,
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = [
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+            ];
+            let instance = meta.instance_column();
+            meta.enable_equality(instance.into());
+            let constant = meta.fixed_column();
+            meta.enable_constant(constant);
+
+            let range_check = R::configure(meta, advice[3]);
+            (LessThanChip::<Fp, R>::configure(meta, advice, range_check), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            R::construct(config.range_check.clone()).load(layouter.namespace(|| "table"))?;
+
+            let a = self.load_private(layouter.namespace(|| "a"), config.advice[0], self.a)?;
+            let b = self.load_private(layouter.namespace(|| "b"), config.advice[0], self.b)?;
+            let lt = LessThanChip::<Fp, R>::construct(config).less_than(layouter.namespace(|| "a < b"), a, b)?;
+            self.expose_public(layouter.namespace(|| "lt"), instance, lt, 0)
+        }
+    }
+
+    fn check<R: TestRangeCheck>() {
+        for (a, b) in [(0, 0), (0, 1), (1, 0), (1234, 4321), (0xffff, 0), (0, 0xffff)] {
+            let circuit = LessThanCircuit::<R> {
+                a: Some(Fp::from(a)),
+                b: Some(Fp::from(b)),
+                _marker: PhantomData,
+            };
+            let lt = Fp::from(a < b);
+            let prover = MockProver::run(6, &circuit, vec![vec![lt]]).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            let prover = MockProver::run(6, &circuit, vec![vec![Fp::one() - lt]]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn less_than() {
+        check::<LookupRangeCheckChip<Fp, 4, 4>>();
+        check::<RangeCheckChip<Fp, 16>>();
+    }
+}
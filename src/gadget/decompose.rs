@@ -0,0 +1,210 @@
+use std::marker::PhantomData;
+
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use crate::utils::{CellValue, Var};
+
+pub trait DecomposeInstructions<F: FieldExt>: Chip<F> {
+    type Cell;
+
+    /// Decomposes `value` into the chip's number of bits, least significant first.
+    ///
+    /// Fails to verify if `value` does not fit in that many bits.
+    fn decompose(&self, layouter: impl Layouter<F>, value: Self::Cell) -> Result<Vec<Self::Cell>, Error>;
+}
+
+/// Configuration for a [`DecomposeChip`].
+#[derive(Clone, Debug)]
+pub struct DecomposeConfig {
+    /// The bit and the accumulated value on each row.
+    pub advice: [Column<Advice>; 2],
+    pub s_bit: Selector,
+}
+
+/// A chip decomposing a value into `NUM_BITS` boolean cells.
+///
+/// The bits are laid out most significant first, each next to the running value
+/// `acc_i = 2 * acc_{i-1} + b_i`, which starts from zero and must end at the value. The
+/// initial zero uses a fixed column enabled for constants.
+#[derive(Clone, Debug)]
+pub struct DecomposeChip<F: FieldExt, const NUM_BITS: usize> {
+    config: DecomposeConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt, const NUM_BITS: usize> Chip<F> for DecomposeChip<F, NUM_BITS> {
+    type Config = DecomposeConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt, const NUM_BITS: usize> DecomposeChip<F, NUM_BITS> {
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 2]) -> DecomposeConfig {
+        for column in &advice {
+            meta.enable_equality((*column).into());
+        }
+
+        let s_bit = meta.selector();
+
+        meta.create_gate("decompose", |meta| {
+            let s_bit = meta.query_selector(s_bit);
+            let bit = meta.query_advice(advice[0], Rotation::cur());
+            let acc_prev = meta.query_advice(advice[1], Rotation::prev());
+            let acc = meta.query_advice(advice[1], Rotation::cur());
+            vec![
+                s_bit.clone() * bit.clone() * (Expression::Constant(F::one()) - bit.clone()),
+                s_bit * (acc_prev * F::from_u64(2) + bit - acc),
+            ]
+        });
+
+        DecomposeConfig { advice, s_bit }
+    }
+
+    pub fn construct(config: DecomposeConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt, const NUM_BITS: usize> DecomposeInstructions<F> for DecomposeChip<F, NUM_BITS> {
+    type Cell = CellValue<F>;
+
+    fn decompose(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: Self::Cell,
+    ) -> Result<Vec<Self::Cell>, Error> {
+        let config = self.config.clone();
+
+        layouter.assign_region(
+            || format!("decompose ({} bits)", NUM_BITS),
+            |mut region| {
+                let mut acc_value = Some(F::zero());
+                let mut acc = region.assign_advice_from_constant(
+                    || "acc_0",
+                    config.advice[1],
+                    0,
+                    F::zero(),
+                )?;
+
+                let bytes = value.value().map(|value| value.to_bytes());
+                let mut bits = Vec::with_capacity(NUM_BITS);
+                for (row, i) in (0..NUM_BITS).rev().enumerate().map(|(row, i)| (row + 1, i)) {
+                    config.s_bit.enable(&mut region, row)?;
+
+                    let bit = bytes.map(|bytes| F::from_u64(((bytes[i / 8] >> (i % 8)) & 1) as u64));
+                    let bit_cell = region.assign_advice(
+                        || format!("b_{}", i),
+                        config.advice[0],
+                        row,
+                        || bit.ok_or(Error::SynthesisError),
+                    )?;
+                    bits.push(CellValue::new(bit_cell, bit));
+
+                    acc_value = acc_value.zip(bit).map(|(acc, bit)| acc.double() + bit);
+                    acc = region.assign_advice(
+                        || format!("acc_{}", row),
+                        config.advice[1],
+                        row,
+                        || acc_value.ok_or(Error::SynthesisError),
+                    )?;
+                }
+
+                region.constrain_equal(acc, value.cell())?;
+
+                bits.reverse();
+                Ok(bits)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+
+    use super::{DecomposeChip, DecomposeConfig, DecomposeInstructions};
+    use crate::utils::{CellValue, UtilitiesInstructions};
+
+    #[derive(Default)]
+    struct DecomposeCircuit {
+        value: Option<Fp>,
+    }
+
+    impl UtilitiesInstructions<Fp> for DecomposeCircuit {
+        type Var = CellValue<Fp>;
+    }
+
+    impl Circuit<Fp> for DecomposeCircuit {
+        type Config = (DecomposeConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = [meta.advice_column(), meta.advice_column()];
+            let instance = meta.instance_column();
+            meta.enable_equality(instance.into());
+            let constant = meta.fixed_column();
+            meta.enable_constant(constant);
+            (DecomposeChip::<Fp, 8>::configure(meta, advice), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let value =
+                self.load_private(layouter.namespace(|| "value"), config.advice[1], self.value)?;
+            let chip = DecomposeChip::<Fp, 8>::construct(config);
+            let bits = chip.decompose(layouter.namespace(|| "decompose"), value)?;
+            for (i, bit) in bits.into_iter().enumerate() {
+                self.expose_public(layouter.namespace(|| format!("bit {}", i)), instance, bit, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn decompose() {
+        let bits = |value: u64| (0..8).map(|i| Fp::from((value >> i) & 1)).collect::<Vec<_>>();
+
+        let circuit = DecomposeCircuit {
+            value: Some(Fp::from(0b1011_0010)),
+        };
+        let prover = MockProver::run(5, &circuit, vec![bits(0b1011_0010)]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let prover = MockProver::run(5, &circuit, vec![bits(0b1011_0011)]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // 256 does not fit in eight bits.
+        let circuit = DecomposeCircuit {
+            value: Some(Fp::from(256)),
+        };
+        let prover = MockProver::run(5, &circuit, vec![bits(0)]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
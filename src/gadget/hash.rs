@@ -0,0 +1,121 @@
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Region},
+    plonk::{Advice, Column, Error},
+};
+
+use super::poseidon::{Poseidon2Chip, Pow5T3Chip, StateWord};
+use crate::primitives::{
+    poseidon::{ConstantLength, Domain, Spec, State},
+    poseidon2::Poseidon2Pow5T3,
+};
+use crate::utils::{copy, CellValue, Var};
+
+/// A 2:1 hash, as used for identity commitments, nullifiers and Merkle tree nodes.
+pub trait HashInstructions<F: FieldExt>: Chip<F> {
+    /// Constructs the chip from its configuration.
+    fn construct(config: Self::Config) -> Self;
+
+    /// Returns the columns [`HashInstructions::hash_in_region`] expects the message in.
+    fn message_columns(config: &Self::Config) -> [Column<Advice>; 2];
+
+    /// Hashes a message the caller has already assigned to the message columns at
+    /// `offset` in `region`. The hash may use that row and the rows that follow it.
+    fn hash_in_region(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        message: [CellValue<F>; 2],
+    ) -> Result<CellValue<F>, Error>;
+
+    /// Hashes the given message, which may live in any equality-enabled columns.
+    fn hash(
+        &self,
+        mut layouter: impl Layouter<F>,
+        message: [CellValue<F>; 2],
+    ) -> Result<CellValue<F>, Error> {
+        let columns = Self::message_columns(self.config());
+        layouter.assign_region(
+            || "hash",
+            |mut region| {
+                let message = [
+                    copy(&mut region, || "message_0", columns[0], 0, &message[0])?,
+                    copy(&mut region, || "message_1", columns[1], 0, &message[1])?,
+                ];
+                self.hash_in_region(&mut region, 0, message)
+            },
+        )
+    }
+}
+
+/// Completes the sponge state for a two-element message at `offset`.
+///
+/// With `ConstantLength<2>` and a rate of 2 the message fills the rate exactly, so after
+/// the first absorption the state is `(m_0, m_1, capacity)`. Loading the capacity next to
+/// the message replaces the separate initial-state and pad-and-add regions.
+fn load_state<F: FieldExt>(
+    region: &mut Region<'_, F>,
+    capacity_column: Column<Advice>,
+    offset: usize,
+    message: [CellValue<F>; 2],
+) -> Result<State<StateWord<F>, 3>, Error> {
+    let capacity = Domain::<F, 3, 2>::initial_capacity_element(&ConstantLength::<2>);
+    let var = region.assign_advice_from_constant(
+        || "load capacity",
+        capacity_column,
+        offset,
+        capacity,
+    )?;
+    Ok([
+        StateWord::new(message[0].cell(), message[0].value()),
+        StateWord::new(message[1].cell(), message[1].value()),
+        StateWord::new(var, Some(capacity)),
+    ])
+}
+
+// `Pow5T3Chip` takes its constants from its config, so it hashes with whichever
+// specification it was configured with.
+impl<F: FieldExt> HashInstructions<F> for Pow5T3Chip<F> {
+    fn construct(config: Self::Config) -> Self {
+        Pow5T3Chip::construct(config)
+    }
+
+    fn message_columns(config: &Self::Config) -> [Column<Advice>; 2] {
+        [config.state[0], config.state[1]]
+    }
+
+    fn hash_in_region(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        message: [CellValue<F>; 2],
+    ) -> Result<CellValue<F>, Error> {
+        let state = load_state(region, self.config().state[2], offset, message)?;
+        let state = self.permute_in_region(region, offset, state)?;
+        Ok(state[0].into())
+    }
+}
+
+impl<F: FieldExt> HashInstructions<F> for Poseidon2Chip<F>
+where
+    Poseidon2Pow5T3: Spec<F, 3, 2>,
+{
+    fn construct(config: Self::Config) -> Self {
+        Poseidon2Chip::construct(config)
+    }
+
+    fn message_columns(config: &Self::Config) -> [Column<Advice>; 2] {
+        [config.state[0], config.state[1]]
+    }
+
+    fn hash_in_region(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        message: [CellValue<F>; 2],
+    ) -> Result<CellValue<F>, Error> {
+        let state = load_state(region, self.config().state[2], offset, message)?;
+        let state = self.permute_in_region(region, offset, state)?;
+        Ok(state[0].into())
+    }
+}
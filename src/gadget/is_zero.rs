@@ -0,0 +1,224 @@
+use std::marker::PhantomData;
+
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use crate::utils::{copy, CellValue, Var};
+
+pub trait IsZeroInstructions<F: FieldExt>: Chip<F> {
+    type Cell;
+
+    /// Returns a cell holding one if `value` is zero, and zero otherwise.
+    fn is_zero(&self, layouter: impl Layouter<F>, value: Self::Cell) -> Result<Self::Cell, Error>;
+
+    /// Returns a cell holding one if `a` equals `b`, and zero otherwise.
+    fn is_equal(
+        &self,
+        layouter: impl Layouter<F>,
+        a: Self::Cell,
+        b: Self::Cell,
+    ) -> Result<Self::Cell, Error>;
+}
+
+/// Configuration for an [`IsZeroChip`].
+#[derive(Clone, Debug)]
+pub struct IsZeroConfig {
+    /// The columns holding `a`, `b`, the inverse of `a - b`, and the result.
+    pub advice: [Column<Advice>; 4],
+    pub s_is_equal: Selector,
+}
+
+/// A chip comparing a value with zero, or two values with each other.
+///
+/// With `d = a - b`, the result is `1 - d * inv`, where the prover witnesses `inv` as the
+/// inverse of `d`, or zero if there is none; `d * result = 0` prevents any other choice.
+/// [`IsZeroInstructions::is_zero`] loads zero as `b` from a fixed column enabled for
+/// constants.
+#[derive(Clone, Debug)]
+pub struct IsZeroChip<F: FieldExt> {
+    config: IsZeroConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> Chip<F> for IsZeroChip<F> {
+    type Config = IsZeroConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt> IsZeroChip<F> {
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 4]) -> IsZeroConfig {
+        for column in &advice {
+            meta.enable_equality((*column).into());
+        }
+
+        let s_is_equal = meta.selector();
+
+        meta.create_gate("is equal", |meta| {
+            let s_is_equal = meta.query_selector(s_is_equal);
+            let a = meta.query_advice(advice[0], Rotation::cur());
+            let b = meta.query_advice(advice[1], Rotation::cur());
+            let inv = meta.query_advice(advice[2], Rotation::cur());
+            let result = meta.query_advice(advice[3], Rotation::cur());
+            let diff = a - b;
+            vec![
+                s_is_equal.clone()
+                    * (Expression::Constant(F::one()) - diff.clone() * inv - result.clone()),
+                s_is_equal * diff * result,
+            ]
+        });
+
+        IsZeroConfig { advice, s_is_equal }
+    }
+
+    pub fn construct(config: IsZeroConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt> IsZeroInstructions<F> for IsZeroChip<F> {
+    type Cell = CellValue<F>;
+
+    fn is_zero(&self, mut layouter: impl Layouter<F>, value: Self::Cell) -> Result<Self::Cell, Error> {
+        let zero = layouter.assign_region(
+            || "load zero",
+            |mut region| {
+                let cell = region.assign_advice_from_constant(
+                    || "zero",
+                    self.config.advice[1],
+                    0,
+                    F::zero(),
+                )?;
+                Ok(CellValue::new(cell, Some(F::zero())))
+            },
+        )?;
+        self.is_equal(layouter, value, zero)
+    }
+
+    fn is_equal(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: Self::Cell,
+        b: Self::Cell,
+    ) -> Result<Self::Cell, Error> {
+        let config = self.config.clone();
+
+        layouter.assign_region(
+            || "is equal",
+            |mut region| {
+                config.s_is_equal.enable(&mut region, 0)?;
+                copy(&mut region, || "a", config.advice[0], 0, &a)?;
+                copy(&mut region, || "b", config.advice[1], 0, &b)?;
+
+                let diff = a.value().zip(b.value()).map(|(a, b)| a - b);
+                let inv = diff.map(|diff| diff.invert().unwrap_or_else(F::zero));
+                region.assign_advice(
+                    || "inv",
+                    config.advice[2],
+                    0,
+                    || inv.ok_or(Error::SynthesisError),
+                )?;
+
+                let result = diff.map(|diff| F::from(bool::from(diff.is_zero())));
+                let cell = region.assign_advice(
+                    || "result",
+                    config.advice[3],
+                    0,
+                    || result.ok_or(Error::SynthesisError),
+                )?;
+                Ok(CellValue::new(cell, result))
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Circuit, Column, ConstraintSystem, Error, Instance},
+    };
+
+    use super::{IsZeroChip, IsZeroConfig, IsZeroInstructions};
+    use crate::utils::{CellValue, UtilitiesInstructions};
+
+    #[derive(Default)]
+    struct IsZeroCircuit {
+        a: Option<Fp>,
+        b: Option<Fp>,
+    }
+
+    impl UtilitiesInstructions<Fp> for IsZeroCircuit {
+        type Var = CellValue<Fp>;
+    }
+
+    impl Circuit<Fp> for IsZeroCircuit {
+        type Config = (IsZeroConfig, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = [
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+            ];
+            let instance = meta.instance_column();
+            meta.enable_equality(instance.into());
+            let constant = meta.fixed_column();
+            meta.enable_constant(constant);
+            (IsZeroChip::configure(meta, advice), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let a = self.load_private(layouter.namespace(|| "a"), config.advice[0], self.a)?;
+            let b = self.load_private(layouter.namespace(|| "b"), config.advice[0], self.b)?;
+            let chip = IsZeroChip::construct(config);
+            let is_zero = chip.is_zero(layouter.namespace(|| "a == 0"), a)?;
+            let is_equal = chip.is_equal(layouter.namespace(|| "a == b"), a, b)?;
+            self.expose_public(layouter.namespace(|| "is_zero"), instance, is_zero, 0)?;
+            self.expose_public(layouter.namespace(|| "is_equal"), instance, is_equal, 1)
+        }
+    }
+
+    #[test]
+    fn is_zero_and_is_equal() {
+        for (a, b) in [(0, 0), (0, 5), (5, 5), (5, 7)] {
+            let circuit = IsZeroCircuit {
+                a: Some(Fp::from(a)),
+                b: Some(Fp::from(b)),
+            };
+            let expected = vec![Fp::from(a == 0), Fp::from(a == b)];
+            let prover = MockProver::run(4, &circuit, vec![expected.clone()]).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+
+            let wrong = vec![expected[0], Fp::one() - expected[1]];
+            let prover = MockProver::run(4, &circuit, vec![wrong]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+}
@@ -1,55 +1,54 @@
 use halo2::{
+    arithmetic::FieldExt,
     circuit::{Chip, Layouter},
     plonk::{Error},
-    pasta::Fp
 };
 
 mod chip;
 pub use chip::{MerkleConfig, MerkleChip};
-use super::super::MERKLE_DEPTH;
 
 
-pub trait MerkleInstructions
-: Chip<Fp> 
+pub trait MerkleInstructions<F: FieldExt>
+: Chip<F> 
 {
     type Cell;
 
     fn hash_layer(
         &self,
-        layouter: impl Layouter<Fp>,
+        layouter: impl Layouter<F>,
         leaf_or_digest: Self::Cell,
-        sibling: Option<Fp>,
-        position_bit: Option<Fp>,
+        sibling: Option<F>,
+        position_bit: Option<F>,
         layer: usize,
     ) -> Result<Self::Cell, Error>;
 
 }
 
 #[derive(Clone, Debug)]
-pub struct MerklePath<MerkleChip> 
-where MerkleChip: MerkleInstructions + Clone,
+pub struct MerklePath<F: FieldExt, MerkleChip, const DEPTH: usize>
+where MerkleChip: MerkleInstructions<F> + Clone,
 {
     pub chip: MerkleChip,
-    pub leaf_pos: Option<[Fp; MERKLE_DEPTH]>,
+    pub leaf_pos: Option<[F; DEPTH]>,
     // The Merkle path is ordered from leaves to root.
-    pub path: Option<[Fp; MERKLE_DEPTH]>,
+    pub path: Option<[F; DEPTH]>,
 }
 
-impl MerklePath<MerkleChip,
-    > where MerkleChip : MerkleInstructions + Clone,
+impl<F: FieldExt, MerkleChip, const DEPTH: usize> MerklePath<F, MerkleChip, DEPTH,
+    > where MerkleChip : MerkleInstructions<F> + Clone,
     {
     pub fn calculate_root(
         &self,
-        mut layouter: impl Layouter<Fp>,
-        leaf: <MerkleChip as MerkleInstructions>::Cell,
-    ) -> Result<<MerkleChip as MerkleInstructions>::Cell, Error> {
+        mut layouter: impl Layouter<F>,
+        leaf: <MerkleChip as MerkleInstructions<F>>::Cell,
+    ) -> Result<<MerkleChip as MerkleInstructions<F>>::Cell, Error> {
         let mut node = leaf;
-        
-        let path = self.path.unwrap();
-        let leaf_pos = self.leaf_pos.unwrap(); 
 
-        for (layer, (sibling, pos)) in path.iter().zip(leaf_pos.iter()).enumerate() {
-            node = self.chip.hash_layer(layouter.namespace(|| format!("hash l {}", layer)), node, Some(*sibling), Some(*pos), layer)?;
+        // The path may be missing when synthesizing without witnesses, e.g. during keygen.
+        for layer in 0..DEPTH {
+            let sibling = self.path.map(|path| path[layer]);
+            let pos = self.leaf_pos.map(|leaf_pos| leaf_pos[layer]);
+            node = self.chip.hash_layer(layouter.namespace(|| format!("hash l {}", layer)), node, sibling, pos, layer)?;
         }
 
         Ok(node)
@@ -1,32 +1,59 @@
+use std::marker::PhantomData;
+
 use halo2::{
+    arithmetic::FieldExt,
     circuit::{Chip, Layouter},
     plonk::{Advice, Column, ConstraintSystem, Error, Selector, Expression},
     poly::Rotation,
-    pasta::Fp
 };
 
 use crate::utils::Var;
 use super::MerkleInstructions;
 use super::super::super::CellValue;
 
-use crate::gadget::poseidon::{Pow5T3Config as PoseidonConfig, Pow5T3Chip as PoseidonChip, Hash as PoseidonHash};
-use crate::primitives::poseidon::{ConstantLength, P128Pow5T3};
+use crate::gadget::hash::HashInstructions;
 
+/// Configuration for a [`MerkleChip`], where `C` is the configuration of its hash chip.
 #[derive(Clone, Debug)]
-pub struct MerkleConfig {
+pub struct MerkleConfig<C> {
     pub advice: [Column<Advice>; 3],
     pub s_bool: Selector,
     pub s_swap: Selector,
-    pub hash_config: PoseidonConfig<Fp>
+    pub hash_config: C
 }
 
-#[derive(Clone, Debug)]
-pub struct MerkleChip{
-    pub config: MerkleConfig,
+/// A chip hashing Merkle paths with the hash chip `H`.
+///
+/// Each layer is a single region: the swap and boolean check of the position bit, with
+/// the swapped pair written into the hash chip's message columns, followed by the hash.
+/// Before, the swap had its own region and the pair was copied into a second region
+/// before the hash. Rows used by [`SemaphoreCircuit`]:
+///
+/// | layout                              | per layer | outside layers | depth 4 | depth 5 |
+/// |-------------------------------------|-----------|----------------|---------|---------|
+/// | separate swap region                | 44        | 87             | 263     | 307     |
+/// | swap in the hash region             | 39        | 79             | 235     | 274     |
+/// | swap in the hash region, Poseidon2  | 25        | 51             | 151     | 176     |
+///
+/// The rows outside the layers also drop, as the identity commitment and nullifier hash
+/// use the same in-region hashing.
+///
+/// [`SemaphoreCircuit`]: crate::SemaphoreCircuit
+#[derive(Debug)]
+pub struct MerkleChip<F: FieldExt, H: HashInstructions<F>> {
+    pub config: MerkleConfig<H::Config>,
+    _marker: PhantomData<(F, H)>,
 }
 
-impl Chip<Fp> for MerkleChip {
-    type Config = MerkleConfig;
+// Implemented by hand, as deriving `Clone` would require `H: Clone`.
+impl<F: FieldExt, H: HashInstructions<F>> Clone for MerkleChip<F, H> {
+    fn clone(&self) -> Self {
+        Self::construct(self.config.clone())
+    }
+}
+
+impl<F: FieldExt, H: HashInstructions<F>> Chip<F> for MerkleChip<F, H> {
+    type Config = MerkleConfig<H::Config>;
     type Loaded = ();
 
     fn config(&self) -> &Self::Config {
@@ -38,12 +65,19 @@ impl Chip<Fp> for MerkleChip {
     }
 }
 
-impl MerkleChip {
+impl<F: FieldExt, H: HashInstructions<F>> MerkleChip<F, H> {
     pub fn configure(
-        meta: &mut ConstraintSystem<Fp>,
+        meta: &mut ConstraintSystem<F>,
         advice: [Column<Advice>; 3],
-        hash_config: PoseidonConfig<Fp>,
-    ) -> <Self as Chip<Fp>>::Config {
+        hash_config: H::Config,
+    ) -> <Self as Chip<F>>::Config {
+        // The swapped pair is hashed in place, so it must sit in the hash's message columns.
+        assert_eq!(
+            [advice[0], advice[1]],
+            H::message_columns(&hash_config),
+            "MerkleChip advice must start with the hash chip's message columns"
+        );
+
         for column in &advice {
             meta.enable_equality((*column).into());
         }
@@ -53,7 +87,7 @@ impl MerkleChip {
         meta.create_gate("bool", |meta| {
             let position_bit = meta.query_advice(advice[2], Rotation::cur());
             let s_bool = meta.query_selector(s_bool);
-            vec![s_bool * position_bit.clone() * (Expression::Constant(Fp::one()) - position_bit)]
+            vec![s_bool * position_bit.clone() * (Expression::Constant(F::one()) - position_bit)]
         });
 
         let s_swap = meta.selector();
@@ -65,11 +99,14 @@ impl MerkleChip {
             let s_swap = meta.query_selector(s_swap);
             let l = meta.query_advice(advice[0], Rotation::next());
             let r = meta.query_advice(advice[1], Rotation::next());
-            vec![s_swap * ((bit * Fp::from(2) * (b.clone() - a.clone()) - (l - a)) - (b - r))]
+            // l = a + bit * (b - a) and r = b + bit * (a - b): with a boolean bit, (l, r) is
+            // either (a, b) or (b, a).
+            vec![
+                s_swap.clone() * (a.clone() + bit.clone() * (b.clone() - a.clone()) - l),
+                s_swap * (b.clone() + bit * (a - b) - r),
+            ]
         });
 
-        let hash_config = hash_config.clone();
-
         MerkleConfig {
             advice,
             s_bool,
@@ -78,31 +115,32 @@ impl MerkleChip {
         }
     }
 
-    pub fn construct(config: <Self as Chip<Fp>>::Config) -> Self {
+    pub fn construct(config: <Self as Chip<F>>::Config) -> Self {
         Self {
             config,
+            _marker: PhantomData,
         }
     }
 }
 // ANCHOR_END: chip-config
 
-impl MerkleInstructions for MerkleChip {
-    type Cell = CellValue<Fp>;
+impl<F: FieldExt, H: HashInstructions<F>> MerkleInstructions<F> for MerkleChip<F, H> {
+    type Cell = CellValue<F>;
 
     fn hash_layer(
         &self,
-        mut layouter: impl Layouter<Fp>,
+        mut layouter: impl Layouter<F>,
         leaf_or_digest: Self::Cell,
-        sibling: Option<Fp>,
-        position_bit: Option<Fp>,
+        sibling: Option<F>,
+        position_bit: Option<F>,
         layer: usize,
     ) -> Result<Self::Cell, Error> {
 
         let config = self.config.clone();
+        let hash_chip = H::construct(config.hash_config.clone());
 
-        let mut left_digest = None;
-        let mut right_digest = None;
-
+        // The swapped pair is assigned straight into the hash chip's message columns, so
+        // the hash starts on the row after the swap instead of in separate regions.
         layouter.assign_region(
             || format!("hash on (layer {})", layer),
             |mut region| {
@@ -116,11 +154,7 @@ impl MerkleInstructions for MerkleChip {
                     row_offset,
                     || left_or_digest_value.ok_or(Error::SynthesisError),
                 )?;
-
-                if layer > 0 {
-                    region.constrain_equal(leaf_or_digest.cell(), left_or_digest_cell)?;
-                    // Should i do permutation here?
-                }
+                region.constrain_equal(leaf_or_digest.cell(), left_or_digest_cell)?;
 
                 let _sibling_cell = region.assign_advice(
                     || format!("witness sibling (layer {})", layer),
@@ -139,11 +173,10 @@ impl MerkleInstructions for MerkleChip {
                 config.s_bool.enable(&mut region, row_offset)?;
                 config.s_swap.enable(&mut region, row_offset)?;
 
-
-                let (l_value, r_value): (Fp, Fp) = if position_bit == Some(Fp::zero()) {
-                    (left_or_digest_value.ok_or(Error::SynthesisError)?, sibling.ok_or(Error::SynthesisError)?)
-                } else {
-                    (sibling.ok_or(Error::SynthesisError)?, left_or_digest_value.ok_or(Error::SynthesisError)?)
+                let (l_value, r_value) = match position_bit {
+                    Some(bit) if bit == F::zero() => (left_or_digest_value, sibling),
+                    Some(_) => (sibling, left_or_digest_value),
+                    None => (None, None),
                 };
 
                 row_offset += 1;
@@ -152,46 +185,193 @@ impl MerkleInstructions for MerkleChip {
                     || format!("witness left (layer {})", layer),
                     config.advice[0],
                     row_offset,
-                    || Ok(l_value),
+                    || l_value.ok_or(Error::SynthesisError),
                 )?;
 
-
                 let r_cell = region.assign_advice(
                     || format!("witness right (layer {})", layer),
                     config.advice[1],
                     row_offset,
-                    || Ok(r_value),
+                    || r_value.ok_or(Error::SynthesisError),
                 )?;
 
-                left_digest = Some(CellValue { cell: l_cell, value: Some(l_value) });
-                right_digest = Some(CellValue { cell: r_cell, value: Some(r_value) });
-
-                Ok(())
+                let message = [
+                    CellValue::new(l_cell, l_value),
+                    CellValue::new(r_cell, r_value),
+                ];
+                hash_chip.hash_in_region(&mut region, row_offset, message)
             },
-        )?;
-
-        let poseidon_chip = PoseidonChip::construct(config.hash_config.clone());
-        let mut poseidon_hasher: PoseidonHash
-        <
-            Fp, 
-            PoseidonChip<Fp>, 
-            P128Pow5T3, 
-            ConstantLength<2_usize>, 
-            3_usize, 
-            2_usize
-        > 
-            = PoseidonHash::init(poseidon_chip, layouter.namespace(|| "init hasher"), ConstantLength::<2>)?;
-
-        let message = [left_digest.unwrap(), right_digest.unwrap()];
-        let loaded_message = poseidon_hasher.witness_message_pieces(
-            config.hash_config.clone(),
-            layouter.namespace(|| format!("witnessing hash of a layer: {}", layer)),
-            message
-        )?;
-
-        let word = poseidon_hasher.hash(layouter.namespace(|| format!("hashing layer: {}", layer)), loaded_message)?;
-        let digest: CellValue<Fp> = word.inner().into();
-
-        Ok(digest)
+        )
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{
+        circuit::{Chip, Layouter, Region, SimpleFloorPlanner},
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance, Selector},
+        poly::Rotation,
+    };
+
+    use super::{MerkleChip, MerkleConfig};
+    use crate::{
+        gadget::{hash::HashInstructions, merkle::MerklePath},
+        utils::{CellValue, UtilitiesInstructions, Var},
+        MERKLE_DEPTH,
+    };
+
+    /// A cheap, insecure stand-in for Poseidon: `H(l, r) = l + 2r`.
+    #[derive(Clone, Debug)]
+    struct AddConfig {
+        advice: [Column<Advice>; 3],
+        s_add: Selector,
+    }
+
+    #[derive(Debug)]
+    struct AddChip {
+        config: AddConfig,
+    }
+
+    impl Chip<Fp> for AddChip {
+        type Config = AddConfig;
+        type Loaded = ();
+
+        fn config(&self) -> &Self::Config {
+            &self.config
+        }
+
+        fn loaded(&self) -> &Self::Loaded {
+            &()
+        }
+    }
+
+    impl AddChip {
+        fn configure(meta: &mut ConstraintSystem<Fp>, advice: [Column<Advice>; 3]) -> AddConfig {
+            let s_add = meta.selector();
+            meta.create_gate("add", |meta| {
+                let l = meta.query_advice(advice[0], Rotation::cur());
+                let r = meta.query_advice(advice[1], Rotation::cur());
+                let out = meta.query_advice(advice[2], Rotation::cur());
+                let s_add = meta.query_selector(s_add);
+                vec![s_add * (l + r * Fp::from(2) - out)]
+            });
+            AddConfig { advice, s_add }
+        }
+
+        fn hash_native(l: Fp, r: Fp) -> Fp {
+            l + r * Fp::from(2)
+        }
+    }
+
+    impl HashInstructions<Fp> for AddChip {
+        fn construct(config: AddConfig) -> Self {
+            AddChip { config }
+        }
+
+        fn message_columns(config: &AddConfig) -> [Column<Advice>; 2] {
+            [config.advice[0], config.advice[1]]
+        }
+
+        fn hash_in_region(
+            &self,
+            region: &mut Region<'_, Fp>,
+            offset: usize,
+            message: [CellValue<Fp>; 2],
+        ) -> Result<CellValue<Fp>, Error> {
+            self.config.s_add.enable(region, offset)?;
+            let value = message[0]
+                .value()
+                .zip(message[1].value())
+                .map(|(l, r)| AddChip::hash_native(l, r));
+            let cell = region.assign_advice(
+                || "digest",
+                self.config.advice[2],
+                offset,
+                || value.ok_or(Error::SynthesisError),
+            )?;
+            Ok(CellValue::new(cell, value))
+        }
+    }
+
+    #[derive(Default)]
+    struct MerkleCircuit {
+        leaf: Option<Fp>,
+        position_bits: Option<[Fp; MERKLE_DEPTH]>,
+        path: Option<[Fp; MERKLE_DEPTH]>,
+    }
+
+    impl UtilitiesInstructions<Fp> for MerkleCircuit {
+        type Var = CellValue<Fp>;
+    }
+
+    impl Circuit<Fp> for MerkleCircuit {
+        type Config = (MerkleConfig<AddConfig>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = [
+                meta.advice_column(),
+                meta.advice_column(),
+                meta.advice_column(),
+            ];
+            let instance = meta.instance_column();
+            meta.enable_equality(instance.into());
+
+            let add_config = AddChip::configure(meta, advice);
+            (
+                MerkleChip::<Fp, AddChip>::configure(meta, advice, add_config),
+                instance,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let leaf =
+                self.load_private(layouter.namespace(|| "leaf"), config.advice[0], self.leaf)?;
+            let merkle_path = MerklePath {
+                chip: MerkleChip::<Fp, AddChip>::construct(config),
+                leaf_pos: self.position_bits,
+                path: self.path,
+            };
+            let root = merkle_path.calculate_root(layouter.namespace(|| "root"), leaf)?;
+            self.expose_public(layouter.namespace(|| "root"), instance, root, 0)
+        }
+    }
+
+    #[test]
+    fn merkle_chip_with_custom_hash() {
+        let leaf = Fp::from(7);
+        let path = [Fp::from(1), Fp::from(2), Fp::from(3), Fp::from(4)];
+        let position_bits = [Fp::zero(), Fp::one(), Fp::one(), Fp::zero()];
+        let root = path
+            .iter()
+            .zip(position_bits.iter())
+            .fold(leaf, |node, (sibling, bit)| {
+                if *bit == Fp::zero() {
+                    AddChip::hash_native(node, *sibling)
+                } else {
+                    AddChip::hash_native(*sibling, node)
+                }
+            });
+
+        let circuit = MerkleCircuit {
+            leaf: Some(leaf),
+            position_bits: Some(position_bits),
+            path: Some(path),
+        };
+        let prover = MockProver::run(5, &circuit, vec![vec![root]]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        let prover = MockProver::run(5, &circuit, vec![vec![root + Fp::one()]]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
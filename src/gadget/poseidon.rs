@@ -1,16 +1,17 @@
-use std::array;
 use std::fmt;
 
 use halo2::{
     arithmetic::FieldExt,
     circuit::{Chip, Layouter},
-    plonk::Error,
+    plonk::{Advice, Column, Error},
 };
 
 mod pow5t3;
 pub use pow5t3::{Pow5T3Chip, Pow5T3Config, StateWord};
+mod poseidon2;
+pub use poseidon2::{Poseidon2Chip, Poseidon2Config};
 use crate::utils::{CellValue, Var};
-use crate::primitives::poseidon::{ConstantLength, Domain, Spec, Sponge, SpongeState, State, P128Pow5T3};
+use crate::primitives::poseidon::{ConstantLength, Domain, Spec, Sponge, SpongeState, State};
 
 /// The set of circuit instructions required to use the Poseidon permutation.
 pub trait PoseidonInstructions<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>:
@@ -76,10 +77,6 @@ impl<
         const RATE: usize,
     > Word<F, PoseidonChip, S, T, RATE>
 {
-    pub(crate) fn inner(&self) -> PoseidonChip::Word {
-        self.inner
-    }
-
     pub(crate) fn from_inner(inner: PoseidonChip::Word) -> Self {
         Self { inner }
     }
@@ -243,7 +240,6 @@ impl<
         const RATE: usize,
         const L: usize,
     > Hash<F, PoseidonChip, S, ConstantLength<L>, T, RATE>
-where P128Pow5T3: Spec<F, 3_usize, 2_usize>
 {
     /// Hashes the given input.
     pub fn hash(
@@ -251,39 +247,51 @@ where P128Pow5T3: Spec<F, 3_usize, 2_usize>
         mut layouter: impl Layouter<F>,
         message: [Word<F, PoseidonChip, S, T, RATE>; L],
     ) -> Result<Word<F, PoseidonChip, S, T, RATE>, Error> {
-        for (i, value) in array::IntoIter::new(message).enumerate() {
+        for (i, value) in message.into_iter().enumerate() {
             self.duplex
                 .absorb(layouter.namespace(|| format!("absorb_{}", i)), value)?;
         }
         self.duplex.squeeze(layouter.namespace(|| "squeeze"))
     }
+}
 
-    pub fn witness_message_pieces( 
+impl<
+        F: FieldExt,
+        PoseidonChip: PoseidonDuplexInstructions<F, S, T, RATE, Word = StateWord<F>>,
+        S: Spec<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+        const L: usize,
+    > Hash<F, PoseidonChip, S, ConstantLength<L>, T, RATE>
+{
+    /// Copies the given message into the chip's `state` columns, ready to be hashed.
+    pub fn witness_message_pieces(
         &mut self,
-        poseidon_config: Pow5T3Config<F>,
+        state: [Column<Advice>; T],
         mut layouter: impl Layouter<F>,
-        message: [CellValue<F>; L]
-    ) -> Result<[Word<F, Pow5T3Chip<F>, P128Pow5T3, 3_usize, 2_usize>; 2_usize], Error> {
-        let poseidon_message = layouter.assign_region(
+        message: [CellValue<F>; L],
+    ) -> Result<[Word<F, PoseidonChip, S, T, RATE>; L], Error> {
+        let words = layouter.assign_region(
             || "load message",
             |mut region| {
-                let mut message_word = |i: usize| {
-                    let value = message[i].value();
-                    let var = region.assign_advice(
-                        || format!("load message_{}", i),
-                        poseidon_config.state[i],
-                        0,
-                        || value.ok_or(Error::SynthesisError),
-                    )?;
-                    region.constrain_equal(var, message[i].cell())?;
-                    Ok(Word::<F, Pow5T3Chip<F>, P128Pow5T3, 3, 2>::from_inner(
-                        StateWord::new(var, value),
-                    ))
-                };
-    
-                Ok([message_word(0)?, message_word(1)?])
+                message
+                    .iter()
+                    .enumerate()
+                    .map(|(i, piece)| {
+                        let value = piece.value();
+                        let var = region.assign_advice(
+                            || format!("load message_{}", i),
+                            state[i % T],
+                            i / T,
+                            || value.ok_or(Error::SynthesisError),
+                        )?;
+                        region.constrain_equal(var, piece.cell())?;
+                        Ok(StateWord::new(var, value))
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
             },
         )?;
-        Ok(poseidon_message)
-    } 
-}
\ No newline at end of file
+        let words: [StateWord<F>; L] = words.try_into().unwrap();
+        Ok(words.map(Word::from_inner))
+    }
+}
@@ -0,0 +1,760 @@
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter, Region},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
+    poly::Rotation,
+};
+
+use super::{
+    pow5t3::{configure_pad_and_add, load_initial_state, load_pad_and_add},
+    PoseidonDuplexInstructions, PoseidonInstructions, StateWord,
+};
+use crate::primitives::{
+    poseidon::{Domain, Mds, Spec, SpongeState, State},
+    poseidon2::{internal_matrix, Poseidon2Pow5T3},
+};
+use crate::utils::Var;
+
+const WIDTH: usize = 3;
+
+/// The number of partial rounds computed by each row.
+const PARTIAL_ROUNDS_PER_ROW: usize = 4;
+
+/// A linear combination of the cells of a partial rounds row, with coefficients for
+/// `[state[1], state[2], partial_sbox, next[0], next[1], next[2]]`.
+type Combination<F> = [F; 6];
+
+/// Expresses the S-box inputs and outputs of the last three partial rounds of a row as
+/// linear combinations of its cells.
+///
+/// The state after the four rounds of a row is linear in `state[1]`, `state[2]` and the
+/// four S-box outputs, and the first output is stored in `partial_sbox`. Solving for the
+/// other three outputs in the next state leaves each S-box as a single constraint of
+/// degree 5, so the rounds need no more columns than the two rounds per row of
+/// [`Pow5T3Chip`].
+///
+/// [`Pow5T3Chip`]: super::Pow5T3Chip
+fn partial_round_combinations<F: FieldExt>(
+    m_int: &Mds<F, WIDTH>,
+) -> ([Combination<F>; 3], [Combination<F>; 3]) {
+    let unit = |i: usize| {
+        let mut combination = [F::zero(); 6];
+        combination[i] = F::one();
+        combination
+    };
+
+    // Run the rounds over `[state[1], state[2], y_1, y_2, y_3, y_4]`, where `y_i` is the
+    // output of the S-box of round `i`.
+    let mut state = [[F::zero(); 6], unit(0), unit(1)];
+    let mut inputs = vec![];
+    for round in 0..PARTIAL_ROUNDS_PER_ROW {
+        if round > 0 {
+            inputs.push(state[0]);
+        }
+        state[0] = unit(2 + round);
+        state = [0, 1, 2].map(|row| {
+            let mut combination = [F::zero(); 6];
+            for (column, word) in state.iter().enumerate() {
+                for (acc, coeff) in combination.iter_mut().zip(word.iter()) {
+                    *acc += m_int[row][column] * coeff;
+                }
+            }
+            combination
+        });
+    }
+
+    // next = p * [state[1], state[2], y_1] + q * [y_2, y_3, y_4], so
+    // [y_2, y_3, y_4] = q^-1 * (next - p * [state[1], state[2], y_1]).
+    let q = state.map(|word| [word[3], word[4], word[5]]);
+    let det = q[0][0] * (q[1][1] * q[2][2] - q[1][2] * q[2][1])
+        - q[0][1] * (q[1][0] * q[2][2] - q[1][2] * q[2][0])
+        + q[0][2] * (q[1][0] * q[2][1] - q[1][1] * q[2][0]);
+    let det_inv = det.invert().unwrap();
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        q[r0][c0] * q[r1][c1] - q[r0][c1] * q[r1][c0]
+    };
+    let outputs = [0, 1, 2].map(|k| {
+        let mut combination = [F::zero(); 6];
+        for (i, word) in state.iter().enumerate() {
+            let q_inv = cofactor(i, k) * det_inv;
+            combination[3 + i] = q_inv;
+            for column in 0..3 {
+                combination[column] -= q_inv * word[column];
+            }
+        }
+        combination
+    });
+
+    let substitute = |word: &[F; 6]| {
+        let mut combination = [word[0], word[1], word[2], F::zero(), F::zero(), F::zero()];
+        for (output, coeff) in outputs.iter().zip(&word[3..]) {
+            for (acc, term) in combination.iter_mut().zip(output.iter()) {
+                *acc += *coeff * term;
+            }
+        }
+        combination
+    };
+    (
+        [
+            substitute(&inputs[0]),
+            substitute(&inputs[1]),
+            substitute(&inputs[2]),
+        ],
+        outputs,
+    )
+}
+
+/// Configuration for a [`Poseidon2Chip`].
+#[derive(Clone, Debug)]
+pub struct Poseidon2Config<F: FieldExt> {
+    pub state: [Column<Advice>; WIDTH],
+    partial_sbox: Column<Advice>,
+    rc_a: [Column<Fixed>; WIDTH],
+    rc_b: [Column<Fixed>; WIDTH],
+    s_first: Selector,
+    s_full: Selector,
+    s_partial: Selector,
+    s_pad_and_add: Selector,
+
+    half_full_rounds: usize,
+    partial_rows: usize,
+    round_constants: Vec<[F; WIDTH]>,
+    m_ext: Mds<F, WIDTH>,
+    m_int: Mds<F, WIDTH>,
+}
+
+/// A Poseidon2 chip using an $x^5$ S-Box, with a width of 3, suitable for a 2:1
+/// reduction.
+///
+/// The chip uses the same columns as [`Pow5T3Chip`], so the two can be swapped without
+/// changing the rest of a circuit. The initial external matrix is folded into the first
+/// full round, and each row of the partial rounds computes four rounds, where
+/// [`Pow5T3Chip`] computes two. A permutation takes 23 rows instead of 37.
+///
+/// [`Pow5T3Chip`]: super::Pow5T3Chip
+#[derive(Debug)]
+pub struct Poseidon2Chip<F: FieldExt> {
+    config: Poseidon2Config<F>,
+}
+
+impl<F: FieldExt> Poseidon2Chip<F>
+where
+    Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+{
+    /// Configures this chip for use in a circuit.
+    ///
+    /// # Side-effects
+    ///
+    /// All columns in `state` will be equality-enabled.
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        spec: Poseidon2Pow5T3,
+        state: [Column<Advice>; WIDTH],
+        partial_sbox: Column<Advice>,
+        rc_a: [Column<Fixed>; WIDTH],
+        rc_b: [Column<Fixed>; WIDTH],
+    ) -> Poseidon2Config<F> {
+        // This gadget requires R_F to be even, and R_P to fill whole rows.
+        assert!(Poseidon2Pow5T3::full_rounds() & 1 == 0);
+        assert!(Poseidon2Pow5T3::partial_rounds() % PARTIAL_ROUNDS_PER_ROW == 0);
+        let half_full_rounds = Poseidon2Pow5T3::full_rounds() / 2;
+        let partial_rows = Poseidon2Pow5T3::partial_rounds() / PARTIAL_ROUNDS_PER_ROW;
+        let (round_constants, m_ext, _) = spec.constants();
+        let m_int = internal_matrix();
+
+        // As in `Pow5T3Chip`, rc_b doubles as scratch space for the padding.
+        for column in state
+            .iter()
+            .map(|c| (*c).into())
+            .chain(rc_b.iter().map(|c| (*c).into()))
+        {
+            meta.enable_equality(column);
+        }
+
+        let s_first = meta.selector();
+        let s_full = meta.selector();
+        let s_partial = meta.selector();
+        let s_pad_and_add = meta.selector();
+
+        let pow_5 = |v: Expression<F>| {
+            let v2 = v.clone() * v.clone();
+            v2.clone() * v2 * v
+        };
+        let mul = |m: &Mds<F, WIDTH>, idx: usize, v: &[Expression<F>; WIDTH]| {
+            v[0].clone() * m[idx][0] + v[1].clone() * m[idx][1] + v[2].clone() * m[idx][2]
+        };
+
+        let mut full_round_gate = |name: &'static str, selector: Selector, first: bool| {
+            meta.create_gate(name, |meta| {
+                let cur = [
+                    meta.query_advice(state[0], Rotation::cur()),
+                    meta.query_advice(state[1], Rotation::cur()),
+                    meta.query_advice(state[2], Rotation::cur()),
+                ];
+                let next = [
+                    meta.query_advice(state[0], Rotation::next()),
+                    meta.query_advice(state[1], Rotation::next()),
+                    meta.query_advice(state[2], Rotation::next()),
+                ];
+                let rc = [
+                    meta.query_fixed(rc_a[0], Rotation::cur()),
+                    meta.query_fixed(rc_a[1], Rotation::cur()),
+                    meta.query_fixed(rc_a[2], Rotation::cur()),
+                ];
+                let selector = meta.query_selector(selector);
+
+                // The first round starts with the initial external linear layer.
+                let input = if first {
+                    [
+                        mul(&m_ext, 0, &cur),
+                        mul(&m_ext, 1, &cur),
+                        mul(&m_ext, 2, &cur),
+                    ]
+                } else {
+                    cur
+                };
+                let sboxed = [
+                    pow_5(input[0].clone() + rc[0].clone()),
+                    pow_5(input[1].clone() + rc[1].clone()),
+                    pow_5(input[2].clone() + rc[2].clone()),
+                ];
+
+                (0..WIDTH)
+                    .map(|idx| selector.clone() * (mul(&m_ext, idx, &sboxed) - next[idx].clone()))
+                    .collect::<Vec<_>>()
+            });
+        };
+        full_round_gate("first full round", s_first, true);
+        full_round_gate("full round", s_full, false);
+
+        let (inputs, outputs) = partial_round_combinations(&m_int);
+        meta.create_gate("partial rounds", |meta| {
+            let cur_0 = meta.query_advice(state[0], Rotation::cur());
+            let cells = [
+                meta.query_advice(state[1], Rotation::cur()),
+                meta.query_advice(state[2], Rotation::cur()),
+                meta.query_advice(partial_sbox, Rotation::cur()),
+                meta.query_advice(state[0], Rotation::next()),
+                meta.query_advice(state[1], Rotation::next()),
+                meta.query_advice(state[2], Rotation::next()),
+            ];
+            let rc = [
+                meta.query_fixed(rc_a[0], Rotation::cur()),
+                meta.query_fixed(rc_a[1], Rotation::cur()),
+                meta.query_fixed(rc_a[2], Rotation::cur()),
+                meta.query_fixed(rc_b[0], Rotation::cur()),
+            ];
+
+            let s_partial = meta.query_selector(s_partial);
+
+            let combine = |combination: &Combination<F>| {
+                cells
+                    .iter()
+                    .zip(combination.iter())
+                    .filter(|(_, coeff)| **coeff != F::zero())
+                    .map(|(cell, coeff)| cell.clone() * *coeff)
+                    .reduce(|acc, term| acc + term)
+                    .unwrap()
+            };
+
+            let mut constraints =
+                vec![s_partial.clone() * (pow_5(cur_0 + rc[0].clone()) - cells[2].clone())];
+            for ((input, output), rc) in inputs.iter().zip(outputs.iter()).zip(rc[1..].iter()) {
+                constraints.push(
+                    s_partial.clone() * (pow_5(combine(input) + rc.clone()) - combine(output)),
+                );
+            }
+            constraints
+        });
+
+        configure_pad_and_add(meta, state, s_pad_and_add);
+
+        Poseidon2Config {
+            state,
+            partial_sbox,
+            rc_a,
+            rc_b,
+            s_first,
+            s_full,
+            s_partial,
+            s_pad_and_add,
+            half_full_rounds,
+            partial_rows,
+            round_constants,
+            m_ext,
+            m_int,
+        }
+    }
+
+    pub fn construct(config: Poseidon2Config<F>) -> Self {
+        Poseidon2Chip { config }
+    }
+
+    /// Applies the permutation to a state that has already been assigned to the `state`
+    /// columns at `offset` in `region`, using the rows that follow it.
+    pub(crate) fn permute_in_region(
+        &self,
+        region: &mut Region<F>,
+        offset: usize,
+        state: State<StateWord<F>, WIDTH>,
+    ) -> Result<State<StateWord<F>, WIDTH>, Error> {
+        let config = &self.config;
+
+        let state = (0..config.half_full_rounds).try_fold(state, |state, r| {
+            full_round(region, config, state, r, offset + r)
+        })?;
+
+        let state = (0..config.partial_rows).try_fold(state, |state, r| {
+            partial_rounds(
+                region,
+                config,
+                state,
+                config.half_full_rounds + PARTIAL_ROUNDS_PER_ROW * r,
+                offset + config.half_full_rounds + r,
+            )
+        })?;
+
+        (0..config.half_full_rounds).try_fold(state, |state, r| {
+            full_round(
+                region,
+                config,
+                state,
+                config.half_full_rounds + PARTIAL_ROUNDS_PER_ROW * config.partial_rows + r,
+                offset + config.half_full_rounds + config.partial_rows + r,
+            )
+        })
+    }
+}
+
+impl<F: FieldExt> Chip<F> for Poseidon2Chip<F> {
+    type Config = Poseidon2Config<F>;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt> PoseidonInstructions<F, Poseidon2Pow5T3, WIDTH, 2> for Poseidon2Chip<F>
+where
+    Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+{
+    type Word = StateWord<F>;
+
+    fn permute(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        initial_state: &State<Self::Word, WIDTH>,
+    ) -> Result<State<Self::Word, WIDTH>, Error> {
+        let config = self.config();
+
+        layouter.assign_region(
+            || "permute state",
+            |mut region| {
+                // Load the initial state into this region.
+                let mut load_state_word = |i: usize| {
+                    let value = initial_state[i].value();
+                    let var = region.assign_advice(
+                        || format!("load state_{}", i),
+                        config.state[i],
+                        0,
+                        || value.ok_or(Error::SynthesisError),
+                    )?;
+                    region.constrain_equal(initial_state[i].cell(), var)?;
+                    Ok(StateWord::new(var, value))
+                };
+                let state = [
+                    load_state_word(0)?,
+                    load_state_word(1)?,
+                    load_state_word(2)?,
+                ];
+
+                self.permute_in_region(&mut region, 0, state)
+            },
+        )
+    }
+}
+
+impl<F: FieldExt> PoseidonDuplexInstructions<F, Poseidon2Pow5T3, WIDTH, 2> for Poseidon2Chip<F>
+where
+    Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+{
+    fn initial_state(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        domain: &impl Domain<F, WIDTH, 2>,
+    ) -> Result<State<Self::Word, WIDTH>, Error> {
+        load_initial_state(layouter, &self.config().state, domain)
+    }
+
+    fn pad_and_add(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        domain: &impl Domain<F, WIDTH, 2>,
+        initial_state: &State<Self::Word, WIDTH>,
+        input: &SpongeState<Self::Word, 2>,
+    ) -> Result<State<Self::Word, WIDTH>, Error> {
+        let config = self.config();
+        load_pad_and_add(
+            layouter,
+            &config.state,
+            &config.rc_b,
+            config.s_pad_and_add,
+            domain,
+            initial_state,
+            input,
+        )
+    }
+
+    fn get_output(state: &State<Self::Word, WIDTH>) -> SpongeState<Self::Word, 2> {
+        [Some(state[0]), Some(state[1])]
+    }
+}
+
+fn mul_vec<F: FieldExt>(m: &Mds<F, WIDTH>, v: [F; WIDTH]) -> [F; WIDTH] {
+    [
+        m[0][0] * v[0] + m[0][1] * v[1] + m[0][2] * v[2],
+        m[1][0] * v[0] + m[1][1] * v[1] + m[1][2] * v[2],
+        m[2][0] * v[0] + m[2][1] * v[1] + m[2][2] * v[2],
+    ]
+}
+
+fn values<F: FieldExt>(state: &State<StateWord<F>, WIDTH>) -> Option<[F; WIDTH]> {
+    state[0].value().and_then(|s_0| {
+        state[1]
+            .value()
+            .and_then(|s_1| state[2].value().map(|s_2| [s_0, s_1, s_2]))
+    })
+}
+
+fn full_round<F: FieldExt>(
+    region: &mut Region<F>,
+    config: &Poseidon2Config<F>,
+    state: State<StateWord<F>, WIDTH>,
+    round: usize,
+    offset: usize,
+) -> Result<State<StateWord<F>, WIDTH>, Error>
+where
+    Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+{
+    let first = round == 0;
+    if first {
+        config.s_first.enable(region, offset)?;
+    } else {
+        config.s_full.enable(region, offset)?;
+    }
+
+    let rcs = config.round_constants[round];
+    for (i, rc) in rcs.iter().enumerate() {
+        region.assign_fixed(
+            || format!("round_{} rc_{}", round, i),
+            config.rc_a[i],
+            offset,
+            || Ok(*rc),
+        )?;
+    }
+
+    let next = values(&state).map(|mut p| {
+        if first {
+            p = mul_vec(&config.m_ext, p);
+        }
+        for (word, rc) in p.iter_mut().zip(rcs.iter()) {
+            *word = Poseidon2Pow5T3::sbox(*word + rc);
+        }
+        mul_vec(&config.m_ext, p)
+    });
+
+    assign_next(region, config, round + 1, offset + 1, next)
+}
+
+fn partial_rounds<F: FieldExt>(
+    region: &mut Region<F>,
+    config: &Poseidon2Config<F>,
+    state: State<StateWord<F>, WIDTH>,
+    round: usize,
+    offset: usize,
+) -> Result<State<StateWord<F>, WIDTH>, Error>
+where
+    Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+{
+    config.s_partial.enable(region, offset)?;
+
+    // Each partial round only uses the first of its round constants.
+    let rcs: Vec<F> = (0..PARTIAL_ROUNDS_PER_ROW)
+        .map(|i| config.round_constants[round + i][0])
+        .collect();
+    let rc_columns = [
+        config.rc_a[0],
+        config.rc_a[1],
+        config.rc_a[2],
+        config.rc_b[0],
+    ];
+    for (i, (column, rc)) in rc_columns.iter().zip(rcs.iter()).enumerate() {
+        region.assign_fixed(
+            || format!("round_{} rc_0", round + i),
+            *column,
+            offset,
+            || Ok(*rc),
+        )?;
+    }
+
+    let p = values(&state);
+    let mid_0 = p.map(|p| Poseidon2Pow5T3::sbox(p[0] + rcs[0]));
+    region.assign_advice(
+        || format!("round_{} partial_sbox", round),
+        config.partial_sbox,
+        offset,
+        || mid_0.ok_or(Error::SynthesisError),
+    )?;
+
+    let next = p.map(|mut p| {
+        for rc in &rcs {
+            p[0] = Poseidon2Pow5T3::sbox(p[0] + rc);
+            p = mul_vec(&config.m_int, p);
+        }
+        p
+    });
+
+    assign_next(
+        region,
+        config,
+        round + PARTIAL_ROUNDS_PER_ROW,
+        offset + 1,
+        next,
+    )
+}
+
+fn assign_next<F: FieldExt>(
+    region: &mut Region<F>,
+    config: &Poseidon2Config<F>,
+    round: usize,
+    offset: usize,
+    next: Option<[F; WIDTH]>,
+) -> Result<State<StateWord<F>, WIDTH>, Error> {
+    let mut next_state_word = |i: usize| {
+        let value = next.map(|next| next[i]);
+        let var = region.assign_advice(
+            || format!("round_{} state_{}", round, i),
+            config.state[i],
+            offset,
+            || value.ok_or(Error::SynthesisError),
+        )?;
+        Ok(StateWord::new(var, value))
+    };
+
+    Ok([
+        next_state_word(0)?,
+        next_state_word(1)?,
+        next_state_word(2)?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use halo2::{
+        arithmetic::FieldExt,
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        plonk::{Circuit, Column, ConstraintSystem, Error, Fixed},
+    };
+    use pasta_curves::{pallas, vesta};
+
+    use super::{Poseidon2Chip, Poseidon2Config, StateWord, WIDTH};
+    use crate::{
+        dev::{used_rows, ConstantColumns},
+        gadget::poseidon::{pow5t3::tests::PermuteCircuit, Hash, PoseidonInstructions, Word},
+        primitives::{
+            poseidon::{self, ConstantLength, Spec},
+            poseidon2::Poseidon2Pow5T3,
+        },
+        utils::Var,
+    };
+
+    fn configure<F: FieldExt>(meta: &mut ConstraintSystem<F>) -> Poseidon2Config<F>
+    where
+        Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        let state = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let partial_sbox = meta.advice_column();
+
+        let rc_a = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+        let rc_b = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+
+        meta.enable_constant(rc_b[0]);
+
+        Poseidon2Chip::configure(meta, Poseidon2Pow5T3, state, partial_sbox, rc_a, rc_b)
+    }
+
+    /// Either permutes `input`, or hashes its first two words, and checks the first
+    /// output word against the native implementation.
+    struct Poseidon2Circuit<F: FieldExt> {
+        input: Option<[F; WIDTH]>,
+        hash: bool,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: FieldExt> Poseidon2Circuit<F>
+    where
+        Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        fn expected_output(&self) -> Option<F> {
+            self.input.map(|mut input| {
+                if self.hash {
+                    poseidon::Hash::init(Poseidon2Pow5T3, ConstantLength::<2>)
+                        .hash([input[0], input[1]])
+                } else {
+                    let (round_constants, mds, _) = Poseidon2Pow5T3.constants();
+                    Poseidon2Pow5T3::permute(&mut input, &mds, &round_constants);
+                    input[0]
+                }
+            })
+        }
+    }
+
+    impl<F: FieldExt> Circuit<F> for Poseidon2Circuit<F>
+    where
+        Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        type Config = Poseidon2Config<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Poseidon2Circuit {
+                input: None,
+                hash: self.hash,
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Poseidon2Config<F> {
+            configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Poseidon2Config<F>,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let chip = Poseidon2Chip::construct(config.clone());
+
+            let input = layouter.assign_region(
+                || "load input",
+                |mut region| {
+                    let mut input_word = |i: usize| {
+                        let value = self.input.map(|input| input[i]);
+                        let var = region.assign_advice(
+                            || format!("load input_{}", i),
+                            config.state[i],
+                            0,
+                            || value.ok_or(Error::SynthesisError),
+                        )?;
+                        Ok(StateWord::new(var, value))
+                    };
+
+                    Ok([input_word(0)?, input_word(1)?, input_word(2)?])
+                },
+            )?;
+
+            let output = if self.hash {
+                let mut hasher =
+                    Hash::init(chip, layouter.namespace(|| "init"), ConstantLength::<2>)?;
+                hasher
+                    .hash(
+                        layouter.namespace(|| "hash"),
+                        [Word::from_inner(input[0]), Word::from_inner(input[1])],
+                    )?
+                    .inner
+            } else {
+                <Poseidon2Chip<F> as PoseidonInstructions<F, Poseidon2Pow5T3, WIDTH, 2>>::permute(
+                    &chip,
+                    &mut layouter,
+                    &input,
+                )?[0]
+            };
+
+            let expected = self.expected_output();
+            layouter.assign_region(
+                || "constrain output",
+                |mut region| {
+                    let expected_var = region.assign_advice(
+                        || "load output",
+                        config.state[0],
+                        0,
+                        || expected.ok_or(Error::SynthesisError),
+                    )?;
+                    region.constrain_equal(output.cell(), expected_var)
+                },
+            )
+        }
+    }
+
+    impl<F: FieldExt> ConstantColumns<F> for Poseidon2Circuit<F>
+    where
+        Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        fn constant_columns(config: &Poseidon2Config<F>) -> Vec<Column<Fixed>> {
+            vec![config.rc_b[0]]
+        }
+    }
+
+    fn check<F: FieldExt>(hash: bool)
+    where
+        Poseidon2Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        let circuit = Poseidon2Circuit::<F> {
+            input: Some([F::rand(), F::rand(), F::rand()]),
+            hash,
+            _marker: PhantomData,
+        };
+
+        let k = 6;
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn poseidon2_permute() {
+        check::<pallas::Base>(false);
+        check::<vesta::Base>(false);
+    }
+
+    #[test]
+    fn poseidon2_hash() {
+        check::<pallas::Base>(true);
+        check::<vesta::Base>(true);
+    }
+
+    #[test]
+    fn fewer_rows_than_pow5t3() {
+        let circuit = Poseidon2Circuit::<pallas::Base> {
+            input: None,
+            hash: false,
+            _marker: PhantomData,
+        };
+
+        // Both circuits use a row to load the input and one to check the output, around
+        // the 37 rows of a Poseidon permutation and the 23 rows of a Poseidon2 one.
+        assert_eq!(used_rows(&PermuteCircuit {}), 39);
+        assert_eq!(used_rows(&circuit), 25);
+    }
+}
@@ -32,7 +32,6 @@ pub struct Pow5T3Config<F: FieldExt> {
     alpha: [u64; 4],
     round_constants: Vec<[F; WIDTH]>,
     m_reg: Mds<F, WIDTH>,
-    m_inv: Mds<F, WIDTH>,
 }
 
 /// A Poseidon chip using an $x^5$ S-Box, with a width of 3, suitable for a 2:1 reduction.
@@ -171,40 +170,7 @@ impl<F: FieldExt> Pow5T3Chip<F> {
             ]
         });
 
-        meta.create_gate("pad-and-add", |meta| {
-            let initial_state_0 = meta.query_advice(state[0], Rotation::prev());
-            let initial_state_1 = meta.query_advice(state[1], Rotation::prev());
-            let initial_state_2 = meta.query_advice(state[2], Rotation::prev());
-            let input_0 = meta.query_advice(state[0], Rotation::cur());
-            let input_1 = meta.query_advice(state[1], Rotation::cur());
-            let output_state_0 = meta.query_advice(state[0], Rotation::next());
-            let output_state_1 = meta.query_advice(state[1], Rotation::next());
-            let output_state_2 = meta.query_advice(state[2], Rotation::next());
-
-            let s_pad_and_add = meta.query_selector(s_pad_and_add);
-
-            let pad_and_add = |initial_state, input, output_state| {
-                // We pad the input by storing the required padding in fixed columns and
-                // then constraining the corresponding input columns to be equal to it.
-                s_pad_and_add.clone() * (initial_state + input - output_state)
-            };
-
-            vec![
-                (
-                    "state[0]",
-                    pad_and_add(initial_state_0, input_0, output_state_0),
-                ),
-                (
-                    "state[1]",
-                    pad_and_add(initial_state_1, input_1, output_state_1),
-                ),
-                // The capacity element is never altered by the input.
-                (
-                    "state[2]",
-                    s_pad_and_add * (initial_state_2 - output_state_2),
-                ),
-            ]
-        });
+        configure_pad_and_add(meta, state, s_pad_and_add);
 
         Pow5T3Config {
             state,
@@ -219,13 +185,48 @@ impl<F: FieldExt> Pow5T3Chip<F> {
             alpha,
             round_constants,
             m_reg,
-            m_inv,
         }
     }
 
     pub fn construct(config: Pow5T3Config<F>) -> Self {
         Pow5T3Chip { config }
     }
+
+    /// Applies the permutation to a state that has already been assigned to the `state`
+    /// columns at `offset` in `region`, using the rows that follow it.
+    pub(crate) fn permute_in_region(
+        &self,
+        region: &mut Region<F>,
+        offset: usize,
+        state: State<StateWord<F>, WIDTH>,
+    ) -> Result<State<StateWord<F>, WIDTH>, Error> {
+        let config = &self.config;
+        let state = Pow5T3State(state);
+
+        let state = (0..config.half_full_rounds).try_fold(state, |state, r| {
+            state.full_round(region, config, r, offset + r)
+        })?;
+
+        let state = (0..config.half_partial_rounds).try_fold(state, |state, r| {
+            state.partial_round(
+                region,
+                config,
+                config.half_full_rounds + 2 * r,
+                offset + config.half_full_rounds + r,
+            )
+        })?;
+
+        let state = (0..config.half_full_rounds).try_fold(state, |state, r| {
+            state.full_round(
+                region,
+                config,
+                config.half_full_rounds + 2 * config.half_partial_rounds + r,
+                offset + config.half_full_rounds + config.half_partial_rounds + r,
+            )
+        })?;
+
+        Ok(state.0)
+    }
 }
 
 impl<F: FieldExt> Chip<F> for Pow5T3Chip<F> {
@@ -257,33 +258,7 @@ impl<F: FieldExt, S: Spec<F, WIDTH, 2>> PoseidonInstructions<F, S, WIDTH, 2> for
                 // Load the initial state into this region.
                 let state = Pow5T3State::load(&mut region, config, initial_state)?;
 
-                let state = (0..config.half_full_rounds).fold(Ok(state), |res, r| {
-                    res.and_then(|state| state.full_round(&mut region, config, r, r))
-                })?;
-
-                let state = (0..config.half_partial_rounds).fold(Ok(state), |res, r| {
-                    res.and_then(|state| {
-                        state.partial_round(
-                            &mut region,
-                            config,
-                            config.half_full_rounds + 2 * r,
-                            config.half_full_rounds + r,
-                        )
-                    })
-                })?;
-
-                let state = (0..config.half_full_rounds).fold(Ok(state), |res, r| {
-                    res.and_then(|state| {
-                        state.full_round(
-                            &mut region,
-                            config,
-                            config.half_full_rounds + 2 * config.half_partial_rounds + r,
-                            config.half_full_rounds + config.half_partial_rounds + r,
-                        )
-                    })
-                })?;
-
-                Ok(state.0)
+                self.permute_in_region(&mut region, 0, state.0)
             },
         )
     }
@@ -297,30 +272,7 @@ impl<F: FieldExt, S: Spec<F, WIDTH, 2>> PoseidonDuplexInstructions<F, S, WIDTH,
         layouter: &mut impl Layouter<F>,
         domain: &impl Domain<F, WIDTH, 2>,
     ) -> Result<State<Self::Word, WIDTH>, Error> {
-        let config = self.config();
-        layouter.assign_region(
-            || format!("initial state for domain {:?}", domain),
-            |mut region| {
-                let mut load_state_word = |i: usize, value: F| {
-                    let var = region.assign_advice_from_constant(
-                        || format!("state_{}", i),
-                        config.state[i],
-                        0,
-                        value,
-                    )?;
-                    Ok(StateWord {
-                        var,
-                        value: Some(value),
-                    })
-                };
-
-                Ok([
-                    load_state_word(0, F::zero())?,
-                    load_state_word(1, F::zero())?,
-                    load_state_word(2, domain.initial_capacity_element())?,
-                ])
-            },
-        )
+        load_initial_state(layouter, &self.config().state, domain)
     }
 
     fn pad_and_add(
@@ -331,83 +283,14 @@ impl<F: FieldExt, S: Spec<F, WIDTH, 2>> PoseidonDuplexInstructions<F, S, WIDTH,
         input: &SpongeState<Self::Word, 2>,
     ) -> Result<State<Self::Word, WIDTH>, Error> {
         let config = self.config();
-        layouter.assign_region(
-            || format!("pad-and-add for domain {:?}", domain),
-            |mut region| {
-                config.s_pad_and_add.enable(&mut region, 1)?;
-
-                // Load the initial state into this region.
-                let mut load_state_word = |i: usize| {
-                    let value = initial_state[i].value;
-                    let var = region.assign_advice(
-                        || format!("load state_{}", i),
-                        config.state[i],
-                        0,
-                        || value.ok_or(Error::SynthesisError),
-                    )?;
-                    region.constrain_equal(initial_state[i].var, var)?;
-                    Ok(StateWord { var, value })
-                };
-                let initial_state = [
-                    load_state_word(0)?,
-                    load_state_word(1)?,
-                    load_state_word(2)?,
-                ];
-
-                let padding_values = domain.padding();
-
-                // Load the input and padding into this region.
-                let mut load_input_word = |i: usize| {
-                    let (constraint_var, value) = match (input[i], padding_values[i]) {
-                        (Some(word), None) => (word.var, word.value),
-                        (None, Some(padding_value)) => {
-                            let padding_var = region.assign_fixed(
-                                || format!("load pad_{}", i),
-                                config.rc_b[i],
-                                1,
-                                || Ok(padding_value),
-                            )?;
-                            (padding_var, Some(padding_value))
-                        }
-                        _ => panic!("Input and padding don't match"),
-                    };
-                    let var = region.assign_advice(
-                        || format!("load input_{}", i),
-                        config.state[i],
-                        1,
-                        || value.ok_or(Error::SynthesisError),
-                    )?;
-                    region.constrain_equal(constraint_var, var)?;
-
-                    Ok(StateWord { var, value })
-                };
-                let input = [load_input_word(0)?, load_input_word(1)?];
-
-                // Constrain the output.
-                let mut constrain_output_word = |i: usize| {
-                    let value = initial_state[i].value.and_then(|initial_word| {
-                        input
-                            .get(i)
-                            .map(|word| word.value)
-                            // The capacity element is never altered by the input.
-                            .unwrap_or_else(|| Some(F::zero()))
-                            .map(|input_word| initial_word + input_word)
-                    });
-                    let var = region.assign_advice(
-                        || format!("load output_{}", i),
-                        config.state[i],
-                        2,
-                        || value.ok_or(Error::SynthesisError),
-                    )?;
-                    Ok(StateWord { var, value })
-                };
-
-                Ok([
-                    constrain_output_word(0)?,
-                    constrain_output_word(1)?,
-                    constrain_output_word(2)?,
-                ])
-            },
+        load_pad_and_add(
+            layouter,
+            &config.state,
+            &config.rc_b,
+            config.s_pad_and_add,
+            domain,
+            initial_state,
+            input,
         )
     }
 
@@ -428,12 +311,190 @@ impl<F: FieldExt> StateWord<F> {
     }
 }
 
+impl<F: FieldExt> Var<F> for StateWord<F> {
+    fn new(var: Cell, value: Option<F>) -> Self {
+        Self { var, value }
+    }
+
+    fn cell(&self) -> Cell {
+        self.var
+    }
+
+    fn value(&self) -> Option<F> {
+        self.value
+    }
+}
+
 impl<F: FieldExt> From<StateWord<F>> for CellValue<F> {
     fn from(state_word: StateWord<F>) -> CellValue<F> {
         CellValue::new(state_word.var, state_word.value)
     }
 }
 
+/// Creates the pad-and-add gate shared by the width-3 Poseidon chips.
+pub(super) fn configure_pad_and_add<F: FieldExt>(
+    meta: &mut ConstraintSystem<F>,
+    state: [Column<Advice>; WIDTH],
+    s_pad_and_add: Selector,
+) {
+    meta.create_gate("pad-and-add", |meta| {
+        let initial_state_0 = meta.query_advice(state[0], Rotation::prev());
+        let initial_state_1 = meta.query_advice(state[1], Rotation::prev());
+        let initial_state_2 = meta.query_advice(state[2], Rotation::prev());
+        let input_0 = meta.query_advice(state[0], Rotation::cur());
+        let input_1 = meta.query_advice(state[1], Rotation::cur());
+        let output_state_0 = meta.query_advice(state[0], Rotation::next());
+        let output_state_1 = meta.query_advice(state[1], Rotation::next());
+        let output_state_2 = meta.query_advice(state[2], Rotation::next());
+
+        let s_pad_and_add = meta.query_selector(s_pad_and_add);
+
+        let pad_and_add = |initial_state, input, output_state| {
+            // We pad the input by storing the required padding in fixed columns and
+            // then constraining the corresponding input columns to be equal to it.
+            s_pad_and_add.clone() * (initial_state + input - output_state)
+        };
+
+        vec![
+            (
+                "state[0]",
+                pad_and_add(initial_state_0, input_0, output_state_0),
+            ),
+            (
+                "state[1]",
+                pad_and_add(initial_state_1, input_1, output_state_1),
+            ),
+            // The capacity element is never altered by the input.
+            (
+                "state[2]",
+                s_pad_and_add * (initial_state_2 - output_state_2),
+            ),
+        ]
+    });
+}
+
+/// Loads the initial sponge state for the given domain from fixed constants.
+pub(super) fn load_initial_state<F: FieldExt>(
+    layouter: &mut impl Layouter<F>,
+    state: &[Column<Advice>; WIDTH],
+    domain: &impl Domain<F, WIDTH, 2>,
+) -> Result<State<StateWord<F>, WIDTH>, Error> {
+    layouter.assign_region(
+        || format!("initial state for domain {:?}", domain),
+        |mut region| {
+            let mut load_state_word = |i: usize, value: F| {
+                let var = region.assign_advice_from_constant(
+                    || format!("state_{}", i),
+                    state[i],
+                    0,
+                    value,
+                )?;
+                Ok(StateWord {
+                    var,
+                    value: Some(value),
+                })
+            };
+
+            Ok([
+                load_state_word(0, F::zero())?,
+                load_state_word(1, F::zero())?,
+                load_state_word(2, domain.initial_capacity_element())?,
+            ])
+        },
+    )
+}
+
+/// Pads the given input and adds it to the state, using `rc_b` to hold the padding.
+pub(super) fn load_pad_and_add<F: FieldExt>(
+    layouter: &mut impl Layouter<F>,
+    state: &[Column<Advice>; WIDTH],
+    rc_b: &[Column<Fixed>; WIDTH],
+    s_pad_and_add: Selector,
+    domain: &impl Domain<F, WIDTH, 2>,
+    initial_state: &State<StateWord<F>, WIDTH>,
+    input: &SpongeState<StateWord<F>, 2>,
+) -> Result<State<StateWord<F>, WIDTH>, Error> {
+    layouter.assign_region(
+        || format!("pad-and-add for domain {:?}", domain),
+        |mut region| {
+            s_pad_and_add.enable(&mut region, 1)?;
+
+            // Load the initial state into this region.
+            let mut load_state_word = |i: usize| {
+                let value = initial_state[i].value;
+                let var = region.assign_advice(
+                    || format!("load state_{}", i),
+                    state[i],
+                    0,
+                    || value.ok_or(Error::SynthesisError),
+                )?;
+                region.constrain_equal(initial_state[i].var, var)?;
+                Ok(StateWord { var, value })
+            };
+            let initial_state = [
+                load_state_word(0)?,
+                load_state_word(1)?,
+                load_state_word(2)?,
+            ];
+
+            let padding_values =
+                domain.padding(input.iter().filter(|word| word.is_some()).count());
+
+            // Load the input and padding into this region.
+            let mut load_input_word = |i: usize| {
+                let (constraint_var, value) = match (input[i], padding_values[i]) {
+                    (Some(word), None) => (word.var, word.value),
+                    (None, Some(padding_value)) => {
+                        let padding_var = region.assign_fixed(
+                            || format!("load pad_{}", i),
+                            rc_b[i],
+                            1,
+                            || Ok(padding_value),
+                        )?;
+                        (padding_var, Some(padding_value))
+                    }
+                    _ => panic!("Input and padding don't match"),
+                };
+                let var = region.assign_advice(
+                    || format!("load input_{}", i),
+                    state[i],
+                    1,
+                    || value.ok_or(Error::SynthesisError),
+                )?;
+                region.constrain_equal(constraint_var, var)?;
+
+                Ok(StateWord { var, value })
+            };
+            let input = [load_input_word(0)?, load_input_word(1)?];
+
+            // Constrain the output.
+            let mut constrain_output_word = |i: usize| {
+                let value = initial_state[i].value.and_then(|initial_word| {
+                    input
+                        .get(i)
+                        .map(|word| word.value)
+                        // The capacity element is never altered by the input.
+                        .unwrap_or_else(|| Some(F::zero()))
+                        .map(|input_word| initial_word + input_word)
+                });
+                let var = region.assign_advice(
+                    || format!("load output_{}", i),
+                    state[i],
+                    2,
+                    || value.ok_or(Error::SynthesisError),
+                )?;
+                Ok(StateWord { var, value })
+            };
+
+            Ok([
+                constrain_output_word(0)?,
+                constrain_output_word(1)?,
+                constrain_output_word(2)?,
+            ])
+        },
+    )
+}
+
 #[derive(Debug)]
 struct Pow5T3State<F: FieldExt>([StateWord<F>; WIDTH]);
 
@@ -615,261 +676,392 @@ impl<F: FieldExt> Pow5T3State<F> {
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use ff::PrimeField;
-//     use halo2::{
-//         arithmetic::FieldExt,
-//         circuit::{Layouter, SimpleFloorPlanner},
-//         dev::MockProver,
-//         pasta::Fp,
-//         plonk::{Circuit, ConstraintSystem, Error},
-//     };
-//     use pasta_curves::pallas;
-
-//     use super::{PoseidonInstructions, Pow5T3Chip, Pow5T3Config, StateWord, WIDTH};
-//     use crate::{
-//         circuit::gadget::poseidon::{Hash, Word},
-//         primitives::poseidon::{self, ConstantLength, P128Pow5T3 as OrchardNullifier, Spec},
-//     };
-
-//     struct PermuteCircuit {}
-
-//     impl Circuit<Fp> for PermuteCircuit {
-//         type Config = Pow5T3Config<Fp>;
-//         type FloorPlanner = SimpleFloorPlanner;
-
-//         fn without_witnesses(&self) -> Self {
-//             PermuteCircuit {}
-//         }
-
-//         fn configure(meta: &mut ConstraintSystem<Fp>) -> Pow5T3Config<Fp> {
-//             let state = [
-//                 meta.advice_column(),
-//                 meta.advice_column(),
-//                 meta.advice_column(),
-//             ];
-//             let partial_sbox = meta.advice_column();
-
-//             let rc_a = [
-//                 meta.fixed_column(),
-//                 meta.fixed_column(),
-//                 meta.fixed_column(),
-//             ];
-//             let rc_b = [
-//                 meta.fixed_column(),
-//                 meta.fixed_column(),
-//                 meta.fixed_column(),
-//             ];
-
-//             Pow5T3Chip::configure(meta, OrchardNullifier, state, partial_sbox, rc_a, rc_b)
-//         }
-
-//         fn synthesize(
-//             &self,
-//             config: Pow5T3Config<Fp>,
-//             mut layouter: impl Layouter<Fp>,
-//         ) -> Result<(), Error> {
-//             let initial_state = layouter.assign_region(
-//                 || "prepare initial state",
-//                 |mut region| {
-//                     let mut state_word = |i: usize| {
-//                         let value = Some(Fp::from(i as u64));
-//                         let var = region.assign_advice(
-//                             || format!("load state_{}", i),
-//                             config.state[i],
-//                             0,
-//                             || value.ok_or(Error::SynthesisError),
-//                         )?;
-//                         Ok(StateWord { var, value })
-//                     };
-
-//                     Ok([state_word(0)?, state_word(1)?, state_word(2)?])
-//                 },
-//             )?;
-
-//             let chip = Pow5T3Chip::construct(config.clone());
-//             let final_state = <Pow5T3Chip<_> as PoseidonInstructions<
-//                 Fp,
-//                 OrchardNullifier,
-//                 WIDTH,
-//                 2,
-//             >>::permute(&chip, &mut layouter, &initial_state)?;
-
-//             // For the purpose of this test, compute the real final state inline.
-//             let mut expected_final_state = [Fp::zero(), Fp::one(), Fp::from_u64(2)];
-//             let (round_constants, mds, _) = OrchardNullifier.constants();
-//             poseidon::permute::<_, OrchardNullifier, WIDTH, 2>(
-//                 &mut expected_final_state,
-//                 &mds,
-//                 &round_constants,
-//             );
-
-//             layouter.assign_region(
-//                 || "constrain final state",
-//                 |mut region| {
-    //                 let mut final_state_word = |i: usize| {
-    //                     let var = region.assign_advice(
-    //                         || format!("load final_state_{}", i),
-    //                         config.state[i],
-    //                         0,
-    //                         || Ok(expected_final_state[i]),
-    //                     )?;
-    //                     region.constrain_equal(final_state[i].var, var)
-    //                 };
-
-    //                 final_state_word(0)?;
-    //                 final_state_word(1)?;
-    //                 final_state_word(2)
-    //             },
-    //         )
-    //     }
-    // }
-
-//     #[test]
-//     fn poseidon_permute() {
-//         let k = 6;
-//         let circuit = PermuteCircuit {};
-//         let prover = MockProver::run(k, &circuit, vec![]).unwrap();
-//         assert_eq!(prover.verify(), Ok(()))
-//     }
-
-//     #[derive(Default)]
-//     struct HashCircuit {
-//         message: Option<[Fp; 2]>,
-//         // For the purpose of this test, witness the result.
-//         // TODO: Move this into an instance column.
-//         output: Option<Fp>,
-//     }
-
-//     impl Circuit<Fp> for HashCircuit {
-//         type Config = Pow5T3Config<Fp>;
-//         type FloorPlanner = SimpleFloorPlanner;
-
-//         fn without_witnesses(&self) -> Self {
-//             Self::default()
-//         }
-
-//         fn configure(meta: &mut ConstraintSystem<Fp>) -> Pow5T3Config<Fp> {
-//             let state = [
-//                 meta.advice_column(),
-//                 meta.advice_column(),
-//                 meta.advice_column(),
-//             ];
-//             let partial_sbox = meta.advice_column();
-
-//             let rc_a = [
-//                 meta.fixed_column(),
-//                 meta.fixed_column(),
-//                 meta.fixed_column(),
-//             ];
-//             let rc_b = [
-//                 meta.fixed_column(),
-//                 meta.fixed_column(),
-//                 meta.fixed_column(),
-//             ];
-
-//             meta.enable_constant(rc_b[0]);
-
-//             Pow5T3Chip::configure(meta, OrchardNullifier, state, partial_sbox, rc_a, rc_b)
-//         }
-
-//         fn synthesize(
-//             &self,
-//             config: Pow5T3Config<Fp>,
-//             mut layouter: impl Layouter<Fp>,
-//         ) -> Result<(), Error> {
-//             let chip = Pow5T3Chip::construct(config.clone());
-
-//             let message = layouter.assign_region(
-//                 || "load message",
-//                 |mut region| {
-//                     let mut message_word = |i: usize| {
-//                         let value = self.message.map(|message_vals| message_vals[i]);
-//                         let var = region.assign_advice(
-//                             || format!("load message_{}", i),
-//                             config.state[i],
-//                             0,
-//                             || value.ok_or(Error::SynthesisError),
-//                         )?;
-//                         Ok(Word::<_, _, OrchardNullifier, WIDTH, 2> {
-//                             inner: StateWord { var, value },
-//                         })
-//                     };
-
-//                     Ok([message_word(0)?, message_word(1)?])
-//                 },
-//             )?;
-
-//             let hasher = Hash::init(chip, layouter.namespace(|| "init"), ConstantLength::<2>)?;
-//             let output = hasher.hash(layouter.namespace(|| "hash"), message)?;
-
-//             layouter.assign_region(
-//                 || "constrain output",
-//                 |mut region| {
-//                     let expected_var = region.assign_advice(
-//                         || "load output",
-//                         config.state[0],
-//                         0,
-//                         || self.output.ok_or(Error::SynthesisError),
-//                     )?;
-//                     let word: StateWord<_> = output.inner;
-//                     region.constrain_equal(word.var, expected_var)
-//                 },
-//             )
-//         }
-//     }
-
-//     #[test]
-//     fn poseidon_hash() {
-//         let message = [Fp::rand(), Fp::rand()];
-//         let output = poseidon::Hash::init(OrchardNullifier, ConstantLength::<2>).hash(message);
-
-//         let k = 6;
-//         let circuit = HashCircuit {
-//             message: Some(message),
-//             output: Some(output),
-//         };
-//         let prover = MockProver::run(k, &circuit, vec![]).unwrap();
-//         assert_eq!(prover.verify(), Ok(()))
-//     }
-
-//     // #[test]
-//     // fn hash_test_vectors() {
-//     //     for tv in crate::primitives::poseidon::test_vectors::fp::hash() {
-//     //         let message = [
-//     //             pallas::Base::from_repr(tv.input[0]).unwrap(),
-//     //             pallas::Base::from_repr(tv.input[1]).unwrap(),
-//     //         ];
-//     //         let output = poseidon::Hash::init(OrchardNullifier, ConstantLength).hash(message);
-
-//     //         let k = 6;
-//     //         let circuit = HashCircuit {
-//     //             message: Some(message),
-//     //             output: Some(output),
-//     //         };
-//     //         let prover = MockProver::run(k, &circuit, vec![]).unwrap();
-//     //         assert_eq!(prover.verify(), Ok(()));
-//     //     }
-//     // }
-
-//     #[cfg(feature = "dev-graph")]
-//     #[test]
-//     fn print_poseidon_chip() {
-//         use plotters::prelude::*;
-
-//         let root = BitMapBackend::new("poseidon-chip-layout.png", (1024, 768)).into_drawing_area();
-//         root.fill(&WHITE).unwrap();
-//         let root = root
-//             .titled("Poseidon Chip Layout", ("sans-serif", 60))
-//             .unwrap();
-
-//         let circuit = HashCircuit {
-//             message: None,
-//             output: None,
-//         };
-//         halo2::dev::CircuitLayout::default()
-//             .render(6, &circuit, &root)
-//             .unwrap();
-//     }
-// }
\ No newline at end of file
+#[cfg(test)]
+pub(super) mod tests {
+    use std::marker::PhantomData;
+
+    use halo2::{
+        arithmetic::FieldExt,
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        plonk::{Circuit, Column, ConstraintSystem, Error, Fixed},
+    };
+    use pasta_curves::{pallas, vesta};
+
+    use super::{PoseidonInstructions, Pow5T3Chip, Pow5T3Config, StateWord, WIDTH};
+    use crate::{
+        dev::ConstantColumns,
+        gadget::poseidon::{Duplex, Hash, Word},
+        primitives::poseidon::{self, ConstantLength, Domain, P128Pow5T3, Spec},
+    };
+
+    fn configure<F: FieldExt>(meta: &mut ConstraintSystem<F>) -> Pow5T3Config<F>
+    where
+        P128Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        let state = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+        let partial_sbox = meta.advice_column();
+
+        let rc_a = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+        let rc_b = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+
+        meta.enable_constant(rc_b[0]);
+
+        Pow5T3Chip::configure(meta, P128Pow5T3, state, partial_sbox, rc_a, rc_b)
+    }
+
+    /// Permutes `[0, 1, 2]` and checks the result against the native implementation.
+    pub(crate) struct PermuteCircuit {}
+
+    impl Circuit<pallas::Base> for PermuteCircuit {
+        type Config = Pow5T3Config<pallas::Base>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            PermuteCircuit {}
+        }
+
+        fn configure(meta: &mut ConstraintSystem<pallas::Base>) -> Pow5T3Config<pallas::Base> {
+            configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Pow5T3Config<pallas::Base>,
+            mut layouter: impl Layouter<pallas::Base>,
+        ) -> Result<(), Error> {
+            let initial_state = layouter.assign_region(
+                || "prepare initial state",
+                |mut region| {
+                    let mut state_word = |i: usize| {
+                        let value = Some(pallas::Base::from(i as u64));
+                        let var = region.assign_advice(
+                            || format!("load state_{}", i),
+                            config.state[i],
+                            0,
+                            || value.ok_or(Error::SynthesisError),
+                        )?;
+                        Ok(StateWord { var, value })
+                    };
+
+                    Ok([state_word(0)?, state_word(1)?, state_word(2)?])
+                },
+            )?;
+
+            let chip = Pow5T3Chip::construct(config.clone());
+            let final_state = <Pow5T3Chip<_> as PoseidonInstructions<
+                pallas::Base,
+                P128Pow5T3,
+                WIDTH,
+                2,
+            >>::permute(&chip, &mut layouter, &initial_state)?;
+
+            // For the purpose of this test, compute the real final state inline.
+            let mut expected_final_state = [
+                pallas::Base::zero(),
+                pallas::Base::one(),
+                pallas::Base::from_u64(2),
+            ];
+            let (round_constants, mds, _) = Spec::<pallas::Base, 3, 2>::constants(&P128Pow5T3);
+            poseidon::permute::<_, P128Pow5T3, WIDTH, 2>(
+                &mut expected_final_state,
+                &mds,
+                &round_constants,
+            );
+
+            layouter.assign_region(
+                || "constrain final state",
+                |mut region| {
+                    let mut final_state_word = |i: usize| {
+                        let var = region.assign_advice(
+                            || format!("load final_state_{}", i),
+                            config.state[i],
+                            0,
+                            || Ok(expected_final_state[i]),
+                        )?;
+                        region.constrain_equal(final_state[i].var, var)
+                    };
+
+                    final_state_word(0)?;
+                    final_state_word(1)?;
+                    final_state_word(2)
+                },
+            )
+        }
+    }
+
+    impl ConstantColumns<pallas::Base> for PermuteCircuit {
+        fn constant_columns(config: &Pow5T3Config<pallas::Base>) -> Vec<Column<Fixed>> {
+            vec![config.rc_b[0]]
+        }
+    }
+
+    #[test]
+    fn poseidon_permute() {
+        let k = 6;
+        let circuit = PermuteCircuit {};
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()))
+    }
+
+    #[derive(Default)]
+    struct HashCircuit {
+        message: Option<[pallas::Base; 2]>,
+        // For the purpose of this test, witness the result.
+        // TODO: Move this into an instance column.
+        output: Option<pallas::Base>,
+    }
+
+    impl Circuit<pallas::Base> for HashCircuit {
+        type Config = Pow5T3Config<pallas::Base>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<pallas::Base>) -> Pow5T3Config<pallas::Base> {
+            configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Pow5T3Config<pallas::Base>,
+            mut layouter: impl Layouter<pallas::Base>,
+        ) -> Result<(), Error> {
+            let chip = Pow5T3Chip::construct(config.clone());
+
+            let message = layouter.assign_region(
+                || "load message",
+                |mut region| {
+                    let mut message_word = |i: usize| {
+                        let value = self.message.map(|message_vals| message_vals[i]);
+                        let var = region.assign_advice(
+                            || format!("load message_{}", i),
+                            config.state[i],
+                            0,
+                            || value.ok_or(Error::SynthesisError),
+                        )?;
+                        Ok(Word::<_, _, P128Pow5T3, WIDTH, 2> {
+                            inner: StateWord { var, value },
+                        })
+                    };
+
+                    Ok([message_word(0)?, message_word(1)?])
+                },
+            )?;
+
+            let mut hasher =
+                Hash::init(chip, layouter.namespace(|| "init"), ConstantLength::<2>)?;
+            let output = hasher.hash(layouter.namespace(|| "hash"), message)?;
+
+            layouter.assign_region(
+                || "constrain output",
+                |mut region| {
+                    let expected_var = region.assign_advice(
+                        || "load output",
+                        config.state[0],
+                        0,
+                        || self.output.ok_or(Error::SynthesisError),
+                    )?;
+                    let word: StateWord<_> = output.inner;
+                    region.constrain_equal(word.var, expected_var)
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn poseidon_hash() {
+        let message = [pallas::Base::rand(), pallas::Base::rand()];
+        let output = poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash(message);
+
+        let k = 6;
+        let circuit = HashCircuit {
+            message: Some(message),
+            output: Some(output),
+        };
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()))
+    }
+
+    #[test]
+    fn hash_test_vectors() {
+        for tv in crate::primitives::poseidon::test_vectors::fp::hash() {
+            let message = [
+                pallas::Base::from_bytes(&tv.input[0]).unwrap(),
+                pallas::Base::from_bytes(&tv.input[1]).unwrap(),
+            ];
+            let output = poseidon::Hash::init(P128Pow5T3, ConstantLength::<2>).hash(message);
+
+            let k = 6;
+            let circuit = HashCircuit {
+                message: Some(message),
+                output: Some(output),
+            };
+            let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        }
+    }
+
+    /// Absorbs `ABSORB` words into a duplex sponge, squeezes `SQUEEZE` words, then
+    /// absorbs and squeezes one more word, checking every squeezed word against the
+    /// native duplex sponge.
+    struct DuplexCircuit<F: FieldExt, const ABSORB: usize, const SQUEEZE: usize> {
+        input: Option<[F; ABSORB]>,
+        _marker: PhantomData<F>,
+    }
+
+    impl<F: FieldExt, const ABSORB: usize, const SQUEEZE: usize> DuplexCircuit<F, ABSORB, SQUEEZE>
+    where
+        P128Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        fn expected_output(&self) -> Option<Vec<F>> {
+            let domain = ConstantLength::<ABSORB>;
+            self.input.map(|input| {
+                let mut duplex = poseidon::Duplex::<_, P128Pow5T3, WIDTH, 2>::new(
+                    P128Pow5T3,
+                    Domain::<F, WIDTH, 2>::initial_capacity_element(&domain),
+                    Domain::<F, WIDTH, 2>::pad_and_add(&domain),
+                );
+                for value in input.iter() {
+                    duplex.absorb(*value);
+                }
+                let mut output: Vec<_> = (0..SQUEEZE).map(|_| duplex.squeeze()).collect();
+                duplex.absorb(input[0]);
+                output.push(duplex.squeeze());
+                output
+            })
+        }
+    }
+
+    impl<F: FieldExt, const ABSORB: usize, const SQUEEZE: usize> Circuit<F>
+        for DuplexCircuit<F, ABSORB, SQUEEZE>
+    where
+        P128Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        type Config = Pow5T3Config<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            DuplexCircuit {
+                input: None,
+                _marker: PhantomData,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Pow5T3Config<F> {
+            configure(meta)
+        }
+
+        fn synthesize(
+            &self,
+            config: Pow5T3Config<F>,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let chip = Pow5T3Chip::construct(config.clone());
+
+            let input = layouter.assign_region(
+                || "load input",
+                |mut region| {
+                    (0..ABSORB)
+                        .map(|i| {
+                            let value = self.input.map(|input| input[i]);
+                            let var = region.assign_advice(
+                                || format!("load input_{}", i),
+                                config.state[i % WIDTH],
+                                i / WIDTH,
+                                || value.ok_or(Error::SynthesisError),
+                            )?;
+                            Ok(StateWord { var, value })
+                        })
+                        .collect::<Result<Vec<_>, Error>>()
+                },
+            )?;
+
+            let mut duplex = Duplex::<_, _, P128Pow5T3, _, WIDTH, 2>::new(
+                chip,
+                layouter.namespace(|| "init"),
+                ConstantLength::<ABSORB>,
+            )?;
+            for (i, word) in input.iter().enumerate() {
+                duplex.absorb(
+                    layouter.namespace(|| format!("absorb_{}", i)),
+                    Word::from_inner(*word),
+                )?;
+            }
+            let mut output = (0..SQUEEZE)
+                .map(|i| duplex.squeeze(layouter.namespace(|| format!("squeeze_{}", i))))
+                .collect::<Result<Vec<_>, Error>>()?;
+            duplex.absorb(
+                layouter.namespace(|| "absorb again"),
+                Word::from_inner(input[0]),
+            )?;
+            output.push(duplex.squeeze(layouter.namespace(|| "squeeze again"))?);
+
+            let expected = self.expected_output();
+            layouter.assign_region(
+                || "constrain output",
+                |mut region| {
+                    for (i, word) in output.iter().enumerate() {
+                        let expected_var = region.assign_advice(
+                            || format!("load output_{}", i),
+                            config.state[i % WIDTH],
+                            i / WIDTH,
+                            || {
+                                expected
+                                    .as_ref()
+                                    .map(|expected| expected[i])
+                                    .ok_or(Error::SynthesisError)
+                            },
+                        )?;
+                        region.constrain_equal(word.inner.var, expected_var)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    fn check_duplex<F: FieldExt, const ABSORB: usize, const SQUEEZE: usize>()
+    where
+        P128Pow5T3: Spec<F, WIDTH, 2>,
+    {
+        let mut input = [F::zero(); ABSORB];
+        for word in input.iter_mut() {
+            *word = F::rand();
+        }
+        let circuit = DuplexCircuit::<F, ABSORB, SQUEEZE> {
+            input: Some(input),
+            _marker: PhantomData,
+        };
+
+        let k = 9;
+        let prover = MockProver::run(k, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn poseidon_duplex() {
+        // A single input word is padded, two fill the rate exactly, and three overflow
+        // it. Squeezing three words exhausts the rate and forces another permutation.
+        check_duplex::<pallas::Base, 1, 1>();
+        check_duplex::<pallas::Base, 2, 3>();
+        check_duplex::<pallas::Base, 3, 2>();
+        check_duplex::<vesta::Base, 1, 1>();
+        check_duplex::<vesta::Base, 2, 3>();
+        check_duplex::<vesta::Base, 3, 2>();
+    }
+}
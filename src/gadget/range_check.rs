@@ -0,0 +1,25 @@
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter},
+    plonk::Error,
+};
+
+mod chip;
+mod lookup;
+pub use chip::{RangeCheckChip, RangeCheckConfig};
+pub use lookup::{LookupRangeCheckChip, LookupRangeCheckConfig};
+
+/// Instructions for range checks, shared by [`RangeCheckChip`] and
+/// [`LookupRangeCheckChip`] so that gadgets can use either.
+pub trait RangeCheckInstructions<F: FieldExt>: Chip<F> {
+    type Cell;
+
+    /// The number of bits `n` the chip checks values against.
+    const NUM_BITS: usize;
+
+    /// Constructs the chip from its configuration.
+    fn construct(config: Self::Config) -> Self;
+
+    /// Constrains `value` to lie in `[0, 2^n)` for the number of bits `n` the chip checks.
+    fn range_check(&self, layouter: impl Layouter<F>, value: Self::Cell) -> Result<(), Error>;
+}
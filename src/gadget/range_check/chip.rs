@@ -0,0 +1,205 @@
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use super::RangeCheckInstructions;
+use crate::utils::{copy, CellValue, Var};
+
+/// Configuration for a [`RangeCheckChip`].
+#[derive(Clone, Debug)]
+pub struct RangeCheckConfig {
+    pub column: Column<Advice>,
+    pub s_bit: Selector,
+}
+
+/// A chip checking that a value fits in `NUM_BITS` bits.
+///
+/// The value is decomposed into a running sum down a single column: `z_0` is the value,
+/// `z_{i+1} = (z_i - b_i) / 2` for a boolean `b_i`, and `z_NUM_BITS` must be zero. The
+/// last constraint uses a fixed column enabled for constants.
+#[derive(Clone, Debug)]
+pub struct RangeCheckChip<F: FieldExt, const NUM_BITS: usize> {
+    config: RangeCheckConfig,
+    _marker: std::marker::PhantomData<F>,
+}
+
+impl<F: FieldExt, const NUM_BITS: usize> Chip<F> for RangeCheckChip<F, NUM_BITS> {
+    type Config = RangeCheckConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt, const NUM_BITS: usize> RangeCheckChip<F, NUM_BITS> {
+    pub fn configure(meta: &mut ConstraintSystem<F>, column: Column<Advice>) -> RangeCheckConfig {
+        meta.enable_equality(column.into());
+
+        let s_bit = meta.selector();
+
+        meta.create_gate("running sum bit", |meta| {
+            let s_bit = meta.query_selector(s_bit);
+            let z_cur = meta.query_advice(column, Rotation::cur());
+            let z_next = meta.query_advice(column, Rotation::next());
+            // b = z_cur - 2 * z_next must be boolean.
+            let bit = z_cur - z_next * F::from_u64(2);
+            vec![s_bit * bit.clone() * (Expression::Constant(F::one()) - bit)]
+        });
+
+        RangeCheckConfig { column, s_bit }
+    }
+}
+
+impl<F: FieldExt, const NUM_BITS: usize> RangeCheckInstructions<F> for RangeCheckChip<F, NUM_BITS> {
+    type Cell = CellValue<F>;
+
+    const NUM_BITS: usize = NUM_BITS;
+
+    fn construct(config: RangeCheckConfig) -> Self {
+        Self {
+            config,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    fn range_check(&self, mut layouter: impl Layouter<F>, value: Self::Cell) -> Result<(), Error> {
+        let config = self.config.clone();
+
+        layouter.assign_region(
+            || format!("range check ({} bits)", NUM_BITS),
+            |mut region| {
+                let mut z = copy(&mut region, || "z_0", config.column, 0, &value)?;
+                let bytes = value.value().map(|value| value.to_bytes());
+                let two_inv = F::from_u64(2).invert().unwrap();
+
+                for i in 0..NUM_BITS {
+                    config.s_bit.enable(&mut region, i)?;
+
+                    let bit = bytes.map(|bytes| F::from_u64(((bytes[i / 8] >> (i % 8)) & 1) as u64));
+                    let next = z.value().zip(bit).map(|(z, bit)| (z - bit) * two_inv);
+                    let cell = region.assign_advice(
+                        || format!("z_{}", i + 1),
+                        config.column,
+                        i + 1,
+                        || next.ok_or(Error::SynthesisError),
+                    )?;
+                    z = CellValue::new(cell, next);
+                }
+
+                // Values of NUM_BITS bits run down to zero.
+                region.constrain_constant(z.cell(), F::zero())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Circuit, ConstraintSystem, Error},
+    };
+
+    use super::{RangeCheckChip, RangeCheckConfig};
+    use crate::{
+        gadget::range_check::RangeCheckInstructions,
+        utils::{CellValue, UtilitiesInstructions},
+    };
+
+    const NUM_BITS: usize = 8;
+    type Chip = RangeCheckChip<Fp, NUM_BITS>;
+
+    #[derive(Default)]
+    struct RangeCheckCircuit {
+        value: Option<Fp>,
+        // Running sum z_1, ..., z_NUM_BITS to assign instead of the chip's own.
+        running_sum: Option<[Fp; NUM_BITS]>,
+    }
+
+    impl UtilitiesInstructions<Fp> for RangeCheckCircuit {
+        type Var = CellValue<Fp>;
+    }
+
+    impl Circuit<Fp> for RangeCheckCircuit {
+        type Config = RangeCheckConfig;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let column = meta.advice_column();
+            let constant = meta.fixed_column();
+            meta.enable_constant(constant);
+            Chip::configure(meta, column)
+        }
+
+        fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<Fp>) -> Result<(), Error> {
+            let running_sum = match self.running_sum {
+                Some(running_sum) => running_sum,
+                None => {
+                    let value = self.load_private(layouter.namespace(|| "value"), config.column, self.value)?;
+                    let chip = Chip::construct(config);
+                    return chip.range_check(layouter.namespace(|| "range check"), value);
+                }
+            };
+
+            layouter.assign_region(
+                || "forged running sum",
+                |mut region| {
+                    region.assign_advice(|| "z_0", config.column, 0, || self.value.ok_or(Error::SynthesisError))?;
+                    let mut last = None;
+                    for (i, z) in running_sum.iter().enumerate() {
+                        config.s_bit.enable(&mut region, i)?;
+                        last = Some(region.assign_advice(|| format!("z_{}", i + 1), config.column, i + 1, || Ok(*z))?);
+                    }
+                    region.constrain_constant(last.unwrap(), Fp::zero())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn range_check() {
+        let max = (1 << NUM_BITS) - 1;
+        for (value, in_range) in [(0, true), (0x5a, true), (max, true), (max + 1, false)] {
+            let circuit = RangeCheckCircuit {
+                value: Some(Fp::from(value)),
+                running_sum: None,
+            };
+            let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+            assert_eq!(prover.verify().is_ok(), in_range, "{:#x}", value);
+        }
+
+        // 2^NUM_BITS runs down to zero in one step with the non-boolean bit 2^NUM_BITS.
+        let circuit = RangeCheckCircuit {
+            value: Some(Fp::from(max + 1)),
+            running_sum: Some([Fp::zero(); NUM_BITS]),
+        };
+        let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // The same region with an honest running sum is accepted.
+        let mut running_sum = [Fp::zero(); NUM_BITS];
+        for (i, z) in running_sum.iter_mut().enumerate() {
+            *z = Fp::from(max >> (i + 1));
+        }
+        let circuit = RangeCheckCircuit {
+            value: Some(Fp::from(max)),
+            running_sum: Some(running_sum),
+        };
+        let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+}
@@ -0,0 +1,214 @@
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Chip, Layouter},
+    plonk::{Advice, Column, ConstraintSystem, Error, Selector, TableColumn},
+    poly::Rotation,
+};
+
+use super::RangeCheckInstructions;
+use crate::utils::{copy, CellValue, Var};
+
+/// Configuration for a [`LookupRangeCheckChip`].
+#[derive(Clone, Debug)]
+pub struct LookupRangeCheckConfig {
+    pub column: Column<Advice>,
+    pub table: TableColumn,
+    pub s_word: Selector,
+}
+
+/// A chip checking that a value fits in `NUM_WORDS` words of `K` bits.
+///
+/// The value is decomposed into a running sum down a single column: `z_0` is the value,
+/// `z_{i+1} = (z_i - w_i) / 2^K` for a word `w_i` looked up in a table of `[0, 2^K)`,
+/// and `z_NUM_WORDS` must be zero. The table must be loaded once per circuit with
+/// [`LookupRangeCheckChip::load_table`], and the last constraint uses a fixed column
+/// enabled for constants.
+#[derive(Clone, Debug)]
+pub struct LookupRangeCheckChip<F: FieldExt, const K: usize, const NUM_WORDS: usize> {
+    config: LookupRangeCheckConfig,
+    _marker: std::marker::PhantomData<F>,
+}
+
+impl<F: FieldExt, const K: usize, const NUM_WORDS: usize> Chip<F>
+    for LookupRangeCheckChip<F, K, NUM_WORDS>
+{
+    type Config = LookupRangeCheckConfig;
+    type Loaded = ();
+
+    fn config(&self) -> &Self::Config {
+        &self.config
+    }
+
+    fn loaded(&self) -> &Self::Loaded {
+        &()
+    }
+}
+
+impl<F: FieldExt, const K: usize, const NUM_WORDS: usize> LookupRangeCheckChip<F, K, NUM_WORDS> {
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        column: Column<Advice>,
+        table: TableColumn,
+    ) -> LookupRangeCheckConfig {
+        meta.enable_equality(column.into());
+
+        let s_word = meta.complex_selector();
+
+        meta.lookup(|meta| {
+            let s_word = meta.query_selector(s_word);
+            let z_cur = meta.query_advice(column, Rotation::cur());
+            let z_next = meta.query_advice(column, Rotation::next());
+            // w = z_cur - 2^K * z_next. Disabled rows look up zero, which is in the table.
+            let word = z_cur - z_next * F::from_u64(1 << K);
+            vec![(s_word * word, table)]
+        });
+
+        LookupRangeCheckConfig {
+            column,
+            table,
+            s_word,
+        }
+    }
+
+    /// Loads the table of `K`-bit words.
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let table = self.config.table;
+        layouter.assign_table(
+            || format!("{}-bit words", K),
+            |mut region| {
+                for word in 0..1 << K {
+                    region.assign_cell(
+                        || format!("word {}", word),
+                        table,
+                        word,
+                        || Ok(F::from_u64(word as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+impl<F: FieldExt, const K: usize, const NUM_WORDS: usize> RangeCheckInstructions<F>
+    for LookupRangeCheckChip<F, K, NUM_WORDS>
+{
+    type Cell = CellValue<F>;
+
+    const NUM_BITS: usize = K * NUM_WORDS;
+
+    fn construct(config: LookupRangeCheckConfig) -> Self {
+        Self {
+            config,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    fn range_check(&self, mut layouter: impl Layouter<F>, value: Self::Cell) -> Result<(), Error> {
+        let config = self.config.clone();
+
+        layouter.assign_region(
+            || format!("range check ({} x {} bits)", NUM_WORDS, K),
+            |mut region| {
+                let mut z = copy(&mut region, || "z_0", config.column, 0, &value)?;
+                let bytes = value.value().map(|value| value.to_bytes());
+                let shift_inv = F::from_u64(1 << K).invert().unwrap();
+
+                for i in 0..NUM_WORDS {
+                    config.s_word.enable(&mut region, i)?;
+
+                    let word = bytes.map(|bytes| {
+                        let word = (0..K)
+                            .map(|j| i * K + j)
+                            .filter(|bit| (bytes[bit / 8] >> (bit % 8)) & 1 == 1)
+                            .fold(0u64, |word, bit| word | 1 << (bit - i * K));
+                        F::from_u64(word)
+                    });
+                    let next = z.value().zip(word).map(|(z, word)| (z - word) * shift_inv);
+                    let cell = region.assign_advice(
+                        || format!("z_{}", i + 1),
+                        config.column,
+                        i + 1,
+                        || next.ok_or(Error::SynthesisError),
+                    )?;
+                    z = CellValue::new(cell, next);
+                }
+
+                // Values of NUM_WORDS words run down to zero.
+                region.constrain_constant(z.cell(), F::zero())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        pasta::Fp,
+        plonk::{Advice, Circuit, Column, ConstraintSystem, Error},
+    };
+
+    use super::{LookupRangeCheckChip, LookupRangeCheckConfig};
+    use crate::{
+        gadget::range_check::RangeCheckInstructions,
+        utils::{CellValue, UtilitiesInstructions},
+    };
+
+    // Four words of four bits.
+    type Chip = LookupRangeCheckChip<Fp, 4, 4>;
+
+    #[derive(Default)]
+    struct RangeCheckCircuit {
+        value: Option<Fp>,
+    }
+
+    impl UtilitiesInstructions<Fp> for RangeCheckCircuit {
+        type Var = CellValue<Fp>;
+    }
+
+    impl Circuit<Fp> for RangeCheckCircuit {
+        type Config = (LookupRangeCheckConfig, Column<Advice>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            let advice = meta.advice_column();
+            let table = meta.lookup_table_column();
+            let constant = meta.fixed_column();
+            meta.enable_constant(constant);
+            (Chip::configure(meta, advice, table), advice)
+        }
+
+        fn synthesize(
+            &self,
+            (config, advice): Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            let chip = Chip::construct(config);
+            chip.load_table(layouter.namespace(|| "table"))?;
+            let value = self.load_private(layouter.namespace(|| "value"), advice, self.value)?;
+            chip.range_check(layouter.namespace(|| "range check"), value)
+        }
+    }
+
+    #[test]
+    fn lookup_range_check() {
+        for (value, in_range) in [(0, true), (0xbeef, true), (0xffff, true), (0x10000, false)] {
+            let circuit = RangeCheckCircuit {
+                value: Some(Fp::from(value)),
+            };
+            let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+            assert_eq!(prover.verify().is_ok(), in_range, "{:#x}", value);
+        }
+        let circuit = RangeCheckCircuit {
+            value: Some(-Fp::one()),
+        };
+        let prover = MockProver::run(6, &circuit, vec![]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! Groups of identities that members can join and be removed from.
+//!
+//! A [`Group`] keeps the tree of identity commitments and the history of its recent
+//! roots. Removing a member overwrites its leaf with the [`empty_leaf`], so proofs
+//! against the new root fail for the removed identity, while the leaves of the other
+//! members keep their indices.
+//!
+//! [`empty_leaf`]: crate::primitives::merkle::empty_leaf
+
+use std::{collections::VecDeque, fmt};
+
+use halo2::arithmetic::FieldExt;
+
+use crate::{
+    primitives::{
+        merkle::{empty_leaf, MerkleTree},
+        poseidon::CachedSpec,
+        HashSpec,
+    },
+    witness::{identity_commitment, PublicInputs},
+    SemaphoreCircuit,
+};
+
+/// The number of roots a [`Group`] remembers, including the current one.
+pub const ROOT_HISTORY_SIZE: usize = 30;
+
+/// The reasons a [`Group`] refuses an operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupError {
+    /// The group tree has no empty leaf left.
+    Full,
+    /// The commitment is the empty leaf, which would make the member look removed.
+    EmptyLeaf,
+    /// No member was ever added at this index.
+    NoSuchMember(usize),
+    /// The member at this index has been removed.
+    Removed(usize),
+    /// The leaf at this index is not the commitment of the given identity.
+    NotMember(usize),
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupError::Full => write!(f, "group is full"),
+            GroupError::EmptyLeaf => write!(f, "commitment is the empty leaf"),
+            GroupError::NoSuchMember(index) => write!(f, "no member at index {}", index),
+            GroupError::Removed(index) => write!(f, "member {} has been removed", index),
+            GroupError::NotMember(index) => write!(f, "member {} is another identity", index),
+        }
+    }
+}
+
+impl std::error::Error for GroupError {}
+
+/// A group of identity commitments, stored in a tree of fixed depth.
+#[derive(Clone, Debug)]
+pub struct Group<F: FieldExt> {
+    tree: MerkleTree<F>,
+    len: usize,
+    // The most recent root is at the back.
+    roots: VecDeque<F>,
+}
+
+impl<F: FieldExt> Group<F>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Creates an empty group with a tree of the given depth.
+    pub fn new(depth: usize) -> Self {
+        let tree = MerkleTree::from_leaves(depth, &[]);
+        let roots = VecDeque::from([tree.root()]);
+        Group {
+            tree,
+            len: 0,
+            roots,
+        }
+    }
+
+    /// Returns the tree of identity commitments.
+    pub fn tree(&self) -> &MerkleTree<F> {
+        &self.tree
+    }
+
+    /// Returns the current root of the group tree.
+    pub fn root(&self) -> F {
+        self.tree.root()
+    }
+
+    /// Returns the number of members ever added, including removed ones.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether `root` is one of the last [`ROOT_HISTORY_SIZE`] roots of the
+    /// group. Verifiers accept proofs against recent roots, which were built before the
+    /// latest changes to the group.
+    ///
+    /// A removed member can still prove against the roots from before its removal until
+    /// they leave the history.
+    pub fn is_known_root(&self, root: F) -> bool {
+        self.roots.contains(&root)
+    }
+
+    /// Adds an identity commitment to the group, and returns its index.
+    pub fn add(&mut self, commitment: F) -> Result<usize, GroupError> {
+        if commitment == empty_leaf() {
+            return Err(GroupError::EmptyLeaf);
+        }
+        if self.len == 1 << self.tree.depth() {
+            return Err(GroupError::Full);
+        }
+        let index = self.len;
+        self.len += 1;
+        self.set(index, commitment);
+        Ok(index)
+    }
+
+    /// Removes the member at `index` by overwriting its leaf with the empty leaf.
+    pub fn remove(&mut self, index: usize) -> Result<(), GroupError> {
+        self.leaf(index)?;
+        self.set(index, empty_leaf());
+        Ok(())
+    }
+
+    /// Returns the commitment of the member at `index`.
+    pub fn leaf(&self, index: usize) -> Result<F, GroupError> {
+        if index >= self.len {
+            return Err(GroupError::NoSuchMember(index));
+        }
+        let leaf = self.tree.leaf(index);
+        if leaf == empty_leaf() {
+            return Err(GroupError::Removed(index));
+        }
+        Ok(leaf)
+    }
+
+    fn set(&mut self, index: usize, leaf: F) {
+        self.tree.set(index, leaf);
+        if self.roots.len() == ROOT_HISTORY_SIZE {
+            self.roots.pop_front();
+        }
+        self.roots.push_back(self.tree.root());
+    }
+}
+
+impl<F: FieldExt, const DEPTH: usize> SemaphoreCircuit<F, DEPTH>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Builds the witness proving that the identity at `index` in `group` signals on
+    /// `topic`, as [`SemaphoreCircuit::from_tree`] does, against the group's current
+    /// root.
+    ///
+    /// This fails if the member has been removed or is another identity, rather than
+    /// building a witness that does not verify.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the group tree is not of depth `DEPTH`.
+    pub fn from_group(
+        identity_trapdoor: F,
+        identity_nullifier: F,
+        topic: &[u8],
+        group: &Group<F>,
+        index: usize,
+    ) -> Result<(Self, PublicInputs<F>), GroupError> {
+        if group.leaf(index)? != identity_commitment(identity_trapdoor, identity_nullifier) {
+            return Err(GroupError::NotMember(index));
+        }
+        Ok(Self::from_tree(identity_trapdoor, identity_nullifier, topic, group.tree(), index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{dev::MockProver, pasta::Fp};
+
+    use super::{Group, GroupError, ROOT_HISTORY_SIZE};
+    use crate::{witness::identity_commitment, SemaphoreCircuit, MERKLE_DEPTH};
+
+    #[test]
+    fn remove_member() {
+        let identities: Vec<_> = (0..3).map(|i| (Fp::from(10 + i), Fp::from(20 + i))).collect();
+        let mut group = Group::new(MERKLE_DEPTH);
+        for (trapdoor, nullifier) in &identities {
+            group.add(identity_commitment(*trapdoor, *nullifier)).unwrap();
+        }
+        let before = group.root();
+
+        group.remove(1).unwrap();
+        assert_eq!(group.remove(1), Err(GroupError::Removed(1)));
+        assert_eq!(group.remove(3), Err(GroupError::NoSuchMember(3)));
+        assert_eq!(group.add(Fp::zero()), Err(GroupError::EmptyLeaf));
+        assert_eq!(group.len(), 3);
+        assert!(group.is_known_root(before));
+        assert_ne!(group.root(), before);
+
+        let (trapdoor, nullifier) = identities[1];
+        let error = SemaphoreCircuit::<Fp, MERKLE_DEPTH>::from_group(trapdoor, nullifier, b"topic", &group, 1);
+        assert_eq!(error.unwrap_err(), GroupError::Removed(1));
+        let error = SemaphoreCircuit::<Fp, MERKLE_DEPTH>::from_group(trapdoor, nullifier, b"topic", &group, 2);
+        assert_eq!(error.unwrap_err(), GroupError::NotMember(2));
+
+        // The other members still prove against the new root.
+        let (trapdoor, nullifier) = identities[2];
+        let (circuit, public_inputs) =
+            SemaphoreCircuit::<Fp, MERKLE_DEPTH>::from_group(trapdoor, nullifier, b"topic", &group, 2).unwrap();
+        assert_eq!(public_inputs.root, group.root());
+        let prover = MockProver::run(10, &circuit, vec![public_inputs.to_instance()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn root_history() {
+        let depth = 5;
+        let mut group = Group::new(depth);
+        let first = group.root();
+        for i in 0..ROOT_HISTORY_SIZE as u64 - 1 {
+            group.add(Fp::from(i + 1)).unwrap();
+        }
+        assert!(group.is_known_root(first));
+        group.remove(0).unwrap();
+        assert!(!group.is_known_root(first));
+
+        while group.len() < 1 << depth {
+            group.add(Fp::one()).unwrap();
+        }
+        assert_eq!(group.add(Fp::one()), Err(GroupError::Full));
+    }
+}
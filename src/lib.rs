@@ -0,0 +1,200 @@
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Layouter, SimpleFloorPlanner},
+    plonk::{Advice, Instance, Circuit, Column, ConstraintSystem, Error, Fixed},
+};
+
+pub mod batch;
+pub mod credential;
+pub mod dev;
+pub mod epoch;
+pub mod ffi;
+pub mod fixtures;
+pub mod primitives;
+pub mod proof;
+pub mod gadget;
+pub mod group;
+pub mod multi_group;
+pub mod nullifier;
+pub mod utils;
+pub mod verifier;
+pub mod voting;
+pub mod wasm;
+pub mod witness;
+
+use gadget:: {
+    hash::HashInstructions,
+    merkle::{MerkleChip, MerkleConfig, MerklePath},
+    HashChip as PoseidonChip, HashConfig as PoseidonConfig,
+};
+
+use crate:: {
+    utils::{UtilitiesInstructions, CellValue},
+    primitives::{poseidon::Spec, HashSpec}
+};
+
+pub const MERKLE_DEPTH: usize = 4;
+
+// Absolute offsets for public inputs.
+const EXTERNAL_NULLIFIER: usize = 0;
+const NULLIFIER_HASH: usize = 1;
+const ROOT: usize = 2;
+
+// Semaphore config
+#[derive(Clone, Debug)]
+pub struct Config<F: FieldExt> {
+    advices: [Column<Advice>; 4],
+    instance: Column<Instance>,
+    merkle_config: MerkleConfig<PoseidonConfig<F>>,
+    poseidon_config: PoseidonConfig<F>,
+    constant: Column<Fixed>,
+}
+
+// Semaphore circuit, over either of the Pasta fields: pallas::Base (Fp) or
+// vesta::Base (Fq).
+// The group tree has depth `DEPTH`, which defaults to MERKLE_DEPTH.
+#[derive(Clone, Debug, Default)]
+pub struct SemaphoreCircuit<F: FieldExt, const DEPTH: usize = MERKLE_DEPTH> {
+    pub identity_trapdoor: Option<F>,
+    pub identity_nullifier: Option<F>,
+    pub external_nullifier: Option<F>,
+    pub position_bits: Option<[F; DEPTH]>,
+    pub path: Option<[F; DEPTH]>,
+    pub root: Option<F>,
+}
+
+impl<F: FieldExt, const DEPTH: usize> UtilitiesInstructions<F> for SemaphoreCircuit<F, DEPTH> {
+    type Var = CellValue<F>;
+}
+
+impl<F: FieldExt, const DEPTH: usize> SemaphoreCircuit<F, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    fn hash(
+        &self,
+        config: Config<F>,
+        mut layouter: impl Layouter<F>,
+        message: [CellValue<F>; 2],
+        to_hash: &str,
+    ) -> Result<CellValue<F>, Error> {
+        let poseidon_chip = config.construct_poseidon_chip();
+        poseidon_chip.hash(layouter.namespace(|| format!("hashing: {}", to_hash)), message)
+    }
+}
+
+impl<F: FieldExt, const DEPTH: usize> Circuit<F> for SemaphoreCircuit<F, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    type Config = Config<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+
+        let advices = [
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+            meta.advice_column(),
+        ];
+
+        let instance = meta.instance_column();
+        meta.enable_equality(instance.into());
+
+        for advice in advices.iter() {
+            meta.enable_equality((*advice).into());
+        }
+
+        let rc_a = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+        let rc_b = [
+            meta.fixed_column(),
+            meta.fixed_column(),
+            meta.fixed_column(),
+        ];
+
+        meta.enable_constant(rc_b[0]);
+
+        let poseidon_config = PoseidonChip::configure(meta, HashSpec, advices[0..3].try_into().unwrap(), advices[3], rc_a, rc_b);
+        let merkle_config = MerkleChip::<F, PoseidonChip<F>>::configure(meta, advices[0..3].try_into().unwrap(), poseidon_config.clone());
+
+        Config {
+            advices, 
+            instance,
+            merkle_config,
+            poseidon_config,
+            constant: rc_b[0],
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+
+        let merkle_chip = config.construct_merkle_chip();
+
+        let identity_trapdoor = self.load_private(
+            layouter.namespace(|| "witness identity_trapdoor"),
+            config.advices[0],
+            self.identity_trapdoor,
+        )?;
+
+        let identity_nullifier = self.load_private(
+            layouter.namespace(|| "witness identity_nullifier"),
+            config.advices[0],
+            self.identity_nullifier,
+        )?;
+
+        let external_nulifier = self.load_private(
+            layouter.namespace(|| "witness external nullifier"),
+            config.advices[0],
+            self.external_nullifier
+        )?;
+
+        let identity_commitment_message = [identity_trapdoor, identity_nullifier];
+        let identity_commitment = self.hash(
+            config.clone(), 
+            layouter.namespace(|| "hash to identity commitment"),
+            identity_commitment_message,
+            "identity commitment"
+        )?;
+
+        // println!("Identity Commitment: {:?}", identity_commitment.value());
+
+        let nullifier_hash_message = [identity_nullifier, external_nulifier];
+        let nullifier_hash = self.hash(
+            config.clone(), 
+            layouter.namespace(|| "hash to nullifier hash"),
+            nullifier_hash_message,
+            "nullifier hash"
+        )?;
+
+        // println!("Nullifier hash: {:?}", nullifier_hash.value());
+
+        let merkle_inputs = MerklePath {
+            chip: merkle_chip,
+            leaf_pos: self.position_bits,
+            path: self.path
+        };
+
+        let calculated_root = merkle_inputs.calculate_root(
+            layouter.namespace(|| "merkle root calculation"),
+            identity_commitment
+        )?;
+        
+        self.expose_public(layouter.namespace(|| "constrain external_nullifier"), config.instance, external_nulifier, EXTERNAL_NULLIFIER)?;
+        self.expose_public(layouter.namespace(|| "constrain nullifier_hash"), config.instance, nullifier_hash, NULLIFIER_HASH)?;
+        self.expose_public(layouter.namespace(|| "constrain root"), config.instance, calculated_root, ROOT)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+//! Proving that one identity is a member of several groups at once.
+//!
+//! [`MultiGroupCircuit`] hashes the identity commitment and the nullifier hash once, as
+//! [`SemaphoreCircuit`] does, and then checks the commitment against `N` group trees,
+//! exposing one root per group.
+
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::{Layouter, SimpleFloorPlanner},
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+
+use crate::{
+    gadget::{hash::HashInstructions, merkle::MerklePath},
+    primitives::{
+        hash_to_field::external_nullifier, merkle::MerkleTree, poseidon::CachedSpec,
+        poseidon::Spec, HashSpec,
+    },
+    utils::{CellValue, UtilitiesInstructions},
+    witness::{identity_commitment, merkle_witness, nullifier_hash},
+    Config, SemaphoreCircuit, EXTERNAL_NULLIFIER, MERKLE_DEPTH, NULLIFIER_HASH, ROOT,
+};
+
+/// A circuit proving that one identity commitment is in each of `N` group trees of depth
+/// `DEPTH`, under a single nullifier.
+///
+/// Its instance column holds the external nullifier, the nullifier hash, and then the
+/// root of each group in order.
+#[derive(Clone, Debug, Default)]
+pub struct MultiGroupCircuit<F: FieldExt, const N: usize, const DEPTH: usize = MERKLE_DEPTH> {
+    pub identity_trapdoor: Option<F>,
+    pub identity_nullifier: Option<F>,
+    pub external_nullifier: Option<F>,
+    pub position_bits: Option<[[F; DEPTH]; N]>,
+    pub paths: Option<[[F; DEPTH]; N]>,
+}
+
+impl<F: FieldExt, const N: usize, const DEPTH: usize> UtilitiesInstructions<F>
+    for MultiGroupCircuit<F, N, DEPTH>
+{
+    type Var = CellValue<F>;
+}
+
+impl<F: FieldExt, const N: usize, const DEPTH: usize> Circuit<F> for MultiGroupCircuit<F, N, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    type Config = Config<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        SemaphoreCircuit::<F, DEPTH>::configure(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let identity_trapdoor = self.load_private(
+            layouter.namespace(|| "witness identity_trapdoor"),
+            config.advices[0],
+            self.identity_trapdoor,
+        )?;
+        let identity_nullifier = self.load_private(
+            layouter.namespace(|| "witness identity_nullifier"),
+            config.advices[0],
+            self.identity_nullifier,
+        )?;
+        let external_nullifier = self.load_private(
+            layouter.namespace(|| "witness external nullifier"),
+            config.advices[0],
+            self.external_nullifier,
+        )?;
+
+        let poseidon_chip = config.construct_poseidon_chip();
+        let identity_commitment = poseidon_chip.hash(
+            layouter.namespace(|| "hash to identity commitment"),
+            [identity_trapdoor, identity_nullifier],
+        )?;
+        let nullifier_hash = poseidon_chip.hash(
+            layouter.namespace(|| "hash to nullifier hash"),
+            [identity_nullifier, external_nullifier],
+        )?;
+
+        for group in 0..N {
+            let merkle_inputs = MerklePath {
+                chip: config.construct_merkle_chip(),
+                leaf_pos: self.position_bits.map(|bits| bits[group]),
+                path: self.paths.map(|paths| paths[group]),
+            };
+            let root = merkle_inputs.calculate_root(
+                layouter.namespace(|| format!("merkle root calculation (group {})", group)),
+                identity_commitment,
+            )?;
+            self.expose_public(
+                layouter.namespace(|| format!("constrain root (group {})", group)),
+                config.instance,
+                root,
+                ROOT + group,
+            )?;
+        }
+
+        self.expose_public(layouter.namespace(|| "constrain external_nullifier"), config.instance, external_nullifier, EXTERNAL_NULLIFIER)?;
+        self.expose_public(layouter.namespace(|| "constrain nullifier_hash"), config.instance, nullifier_hash, NULLIFIER_HASH)
+    }
+}
+
+/// The public inputs of [`MultiGroupCircuit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MultiGroupPublicInputs<F: FieldExt, const N: usize> {
+    pub external_nullifier: F,
+    pub nullifier_hash: F,
+    pub roots: [F; N],
+}
+
+impl<F: FieldExt, const N: usize> MultiGroupPublicInputs<F, N> {
+    /// Returns the instance column expected by [`MultiGroupCircuit`].
+    pub fn to_instance(&self) -> Vec<F> {
+        let mut instance = vec![F::zero(); ROOT + N];
+        instance[EXTERNAL_NULLIFIER] = self.external_nullifier;
+        instance[NULLIFIER_HASH] = self.nullifier_hash;
+        instance[ROOT..].copy_from_slice(&self.roots);
+        instance
+    }
+}
+
+impl<F: FieldExt, const N: usize, const DEPTH: usize> MultiGroupCircuit<F, N, DEPTH>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Builds the witness proving that the identity is the leaf at `indices[i]` in
+    /// `trees[i]` for every group, and signals on `topic`, together with the matching
+    /// public inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any tree is not of depth `DEPTH`, or if any of the leaves is not the
+    /// commitment to the given identity.
+    pub fn from_trees(
+        identity_trapdoor: F,
+        identity_nullifier: F,
+        topic: &[u8],
+        trees: [&MerkleTree<F>; N],
+        indices: [usize; N],
+    ) -> (Self, MultiGroupPublicInputs<F, N>) {
+        let commitment = identity_commitment(identity_trapdoor, identity_nullifier);
+
+        let mut paths = [[F::zero(); DEPTH]; N];
+        let mut position_bits = [[F::zero(); DEPTH]; N];
+        for group in 0..N {
+            let (path, bits) = merkle_witness(commitment, trees[group], indices[group]);
+            paths[group] = path;
+            position_bits[group] = bits;
+        }
+
+        let external_nullifier = external_nullifier(topic);
+        let public_inputs = MultiGroupPublicInputs {
+            external_nullifier,
+            nullifier_hash: nullifier_hash(identity_nullifier, external_nullifier),
+            roots: trees.map(|tree| tree.root()),
+        };
+        let circuit = MultiGroupCircuit {
+            identity_trapdoor: Some(identity_trapdoor),
+            identity_nullifier: Some(identity_nullifier),
+            external_nullifier: Some(external_nullifier),
+            position_bits: Some(position_bits),
+            paths: Some(paths),
+        };
+
+        (circuit, public_inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{dev::MockProver, pasta::Fp};
+
+    use super::MultiGroupCircuit;
+    use crate::{
+        dev::used_rows, primitives::merkle::MerkleTree, witness::identity_commitment,
+        SemaphoreCircuit, MERKLE_DEPTH,
+    };
+
+    #[test]
+    fn member_of_two_groups() {
+        let (trapdoor, nullifier) = (Fp::from(2), Fp::from(3));
+        let commitment = identity_commitment(trapdoor, nullifier);
+
+        let humans = MerkleTree::from_leaves(MERKLE_DEPTH, &[Fp::from(7), commitment]);
+        let mut members: Vec<_> = (0..9).map(|i| Fp::from(100 + i)).collect();
+        members[6] = commitment;
+        let dao = MerkleTree::from_leaves(MERKLE_DEPTH, &members);
+
+        let (circuit, public_inputs) = MultiGroupCircuit::<Fp, 2>::from_trees(
+            trapdoor,
+            nullifier,
+            b"topic",
+            [&humans, &dao],
+            [1, 6],
+        );
+        let prover = MockProver::run(10, &circuit, vec![public_inputs.to_instance()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // The proof does not hold against another root for the second group.
+        let mut other = public_inputs;
+        other.roots[1] = MerkleTree::from_leaves(MERKLE_DEPTH, &[commitment]).root();
+        let prover = MockProver::run(10, &circuit, vec![other.to_instance()]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // Only the Merkle layers are repeated for the second group.
+        let single = SemaphoreCircuit::<Fp>::default();
+        let one_group = MultiGroupCircuit::<Fp, 1>::default();
+        assert_eq!(used_rows(&one_group), used_rows(&single));
+        assert!(used_rows(&MultiGroupCircuit::<Fp, 2>::default()) < 2 * used_rows(&single));
+    }
+}
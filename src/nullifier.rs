@@ -0,0 +1,41 @@
+//! Nullifier hashes that have already been used.
+//!
+//! A Semaphore nullifier hash is derived from the identity nullifier and the external
+//! nullifier, so an identity produces the same nullifier hash every time it signals on
+//! the same topic. A verifier that records the nullifier hashes of the proofs it accepts
+//! rejects a second signal from the same member on that topic.
+
+use std::collections::HashSet;
+
+use halo2::{arithmetic::FieldExt, pasta::Fp};
+
+/// An in-memory set of used nullifier hashes.
+#[derive(Clone, Debug, Default)]
+pub struct NullifierStore {
+    used: HashSet<[u8; 32]>,
+}
+
+impl NullifierStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether `nullifier_hash` has been used.
+    pub fn contains(&self, nullifier_hash: Fp) -> bool {
+        self.used.contains(&nullifier_hash.to_bytes())
+    }
+
+    /// Records `nullifier_hash` as used, and returns `false` if it already was.
+    pub fn insert(&mut self, nullifier_hash: Fp) -> bool {
+        self.used.insert(nullifier_hash.to_bytes())
+    }
+
+    /// Returns the number of used nullifier hashes.
+    pub fn len(&self) -> usize {
+        self.used.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.used.is_empty()
+    }
+}
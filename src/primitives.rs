@@ -1 +1,20 @@
-pub mod poseidon;
\ No newline at end of file
+pub mod hash_to_field;
+pub mod merkle;
+pub mod poseidon;
+pub mod poseidon2;
+
+/// The hash used for identity commitments, nullifiers and Merkle nodes.
+///
+/// This is [`poseidon::P128Pow5T3`] unless the `poseidon2` feature is enabled.
+#[cfg(not(feature = "poseidon2"))]
+pub use poseidon::P128Pow5T3 as HashSpec;
+/// The hash used for identity commitments, nullifiers and Merkle nodes.
+#[cfg(feature = "poseidon2")]
+pub use poseidon2::Poseidon2Pow5T3 as HashSpec;
+
+/// The name of [`HashSpec`], which identifies the hash in circuit identifiers.
+#[cfg(not(feature = "poseidon2"))]
+pub const HASH_SPEC_NAME: &str = "P128Pow5T3";
+/// The name of [`HashSpec`], which identifies the hash in circuit identifiers.
+#[cfg(feature = "poseidon2")]
+pub const HASH_SPEC_NAME: &str = "Poseidon2Pow5T3";
@@ -0,0 +1,65 @@
+//! Hashing arbitrary byte strings to field elements.
+//!
+//! External nullifiers and identity secrets are usually strings or byte blobs, while the
+//! circuit only accepts field elements. [`hash_bytes_to_field`] maps a byte string to a field
+//! element by taking a personalised Blake2b-512 digest and reducing it with
+//! [`FieldExt::from_bytes_wide`]. Reducing 512 bits makes the bias from the modular
+//! reduction negligible, and the personalisation keeps each use in its own domain: the
+//! same bytes used as a topic and as an identity secret hash to unrelated elements.
+
+use blake2b_simd::Params;
+use halo2::arithmetic::FieldExt;
+
+/// Blake2b personalisation for [`external_nullifier`].
+pub const EXTERNAL_NULLIFIER_PERSONALIZATION: &[u8; 16] = b"Semaphore_ExtNul";
+
+/// Blake2b personalisation for the trapdoor in [`identity_from_secret`].
+///
+/// [`identity_from_secret`]: crate::witness::identity_from_secret
+pub const IDENTITY_TRAPDOOR_PERSONALIZATION: &[u8; 16] = b"Semaphore_IdTrap";
+
+/// Blake2b personalisation for the nullifier in [`identity_from_secret`].
+///
+/// [`identity_from_secret`]: crate::witness::identity_from_secret
+pub const IDENTITY_NULLIFIER_PERSONALIZATION: &[u8; 16] = b"Semaphore_IdNull";
+
+/// Hashes `message` to a field element within the domain given by `personalization`.
+pub fn hash_bytes_to_field<F: FieldExt>(personalization: &[u8; 16], message: &[u8]) -> F {
+    let hash = Params::new()
+        .hash_length(64)
+        .personal(personalization)
+        .hash(message);
+    let mut bytes = [0; 64];
+    bytes.copy_from_slice(hash.as_bytes());
+    F::from_bytes_wide(&bytes)
+}
+
+/// Returns the external nullifier for the given topic, such as a poll identifier or a
+/// URL.
+pub fn external_nullifier<F: FieldExt>(topic: &[u8]) -> F {
+    hash_bytes_to_field(EXTERNAL_NULLIFIER_PERSONALIZATION, topic)
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::pasta::Fp;
+
+    use super::{external_nullifier, hash_bytes_to_field, IDENTITY_TRAPDOOR_PERSONALIZATION};
+
+    #[test]
+    fn domain_separation() {
+        let topic = b"https://example.com/poll/1";
+        let nullifier: Fp = external_nullifier(topic);
+        assert_eq!(nullifier, external_nullifier(topic));
+        assert_ne!(
+            nullifier,
+            hash_bytes_to_field(IDENTITY_TRAPDOOR_PERSONALIZATION, topic)
+        );
+        assert_ne!(nullifier, external_nullifier(b"https://example.com/poll/2"));
+        assert_ne!(
+            hash_bytes_to_field::<Fp>(b"0123456789abcdef", b""),
+            hash_bytes_to_field::<Fp>(b"0123456789abcdeg", b"")
+        );
+        assert_ne!(external_nullifier::<Fp>(b""), Fp::zero());
+    }
+}
@@ -0,0 +1,200 @@
+//! A native Poseidon Merkle tree, hashing layers the same way as [`MerkleChip`].
+//!
+//! Nodes are hashed with [`HashSpec`], so the tree follows the `poseidon2` feature.
+//!
+//! [`MerkleChip`]: crate::gadget::merkle::MerkleChip
+
+use halo2::arithmetic::FieldExt;
+use rayon::prelude::*;
+
+use super::{
+    poseidon::{CachedSpec, ConstantLength, PoseidonHasher},
+    HashSpec,
+};
+
+type NodeHasher<F> = PoseidonHasher<F, HashSpec, ConstantLength<2>, 3, 2>;
+
+/// Returns the value of an empty leaf, which is zero in either field.
+pub fn empty_leaf<F: FieldExt>() -> F {
+    F::zero()
+}
+
+/// A fixed-depth Poseidon Merkle tree.
+///
+/// Only the occupied prefix of each level is stored; the remaining nodes are the roots
+/// of empty subtrees, which are computed once per tree.
+#[derive(Clone, Debug)]
+pub struct MerkleTree<F: FieldExt> {
+    depth: usize,
+    // levels[0] holds the leaves and levels[depth] holds the root.
+    levels: Vec<Vec<F>>,
+    // zeros[i] is the root of an empty subtree of height i.
+    zeros: Vec<F>,
+}
+
+impl<F: FieldExt> MerkleTree<F>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Builds a tree of the given depth from its leaves, hashing each level in parallel.
+    ///
+    /// Leaves past `leaves.len()` are empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `2^depth` leaves.
+    pub fn from_leaves(depth: usize, leaves: &[F]) -> Self {
+        assert!(leaves.len() <= 1 << depth, "too many leaves for a tree of depth {}", depth);
+
+        let hasher = NodeHasher::new(ConstantLength::<2>);
+
+        let mut zeros = Vec::with_capacity(depth + 1);
+        zeros.push(empty_leaf());
+        for i in 0..depth {
+            zeros.push(hasher.hash([zeros[i], zeros[i]]));
+        }
+
+        let mut levels = Vec::with_capacity(depth + 1);
+        levels.push(leaves.to_vec());
+        for i in 0..depth {
+            let zero = zeros[i];
+            let parents = levels[i]
+                .par_chunks(2)
+                .map(|pair: &[F]| hasher.hash([pair[0], *pair.get(1).unwrap_or(&zero)]))
+                .collect();
+            levels.push(parents);
+        }
+
+        MerkleTree {
+            depth,
+            levels,
+            zeros,
+        }
+    }
+
+    /// Returns the depth of this tree.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the root of this tree.
+    pub fn root(&self) -> F {
+        self.node(self.depth, 0)
+    }
+
+    /// Returns the leaf at `index`.
+    pub fn leaf(&self, index: usize) -> F {
+        self.node(0, index)
+    }
+
+    /// Returns the Merkle path and position bits for the leaf at `index`, ordered from
+    /// leaves to root, in the form expected by [`SemaphoreCircuit`].
+    ///
+    /// [`SemaphoreCircuit`]: crate::SemaphoreCircuit
+    pub fn path(&self, index: usize) -> (Vec<F>, Vec<F>) {
+        assert!(index < 1 << self.depth, "leaf index {} out of range", index);
+
+        (0..self.depth)
+            .map(|level| {
+                let pos = index >> level;
+                (self.node(level, pos ^ 1), F::from_u64((pos & 1) as u64))
+            })
+            .unzip()
+    }
+
+    /// Replaces the leaf at `index`, rehashing the nodes above it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not a leaf of this tree.
+    pub fn set(&mut self, index: usize, leaf: F) {
+        assert!(index < 1 << self.depth, "leaf index {} out of range", index);
+
+        let hasher = NodeHasher::new(ConstantLength::<2>);
+        let mut node = leaf;
+        for level in 0..=self.depth {
+            let pos = index >> level;
+            let nodes = &mut self.levels[level];
+            if nodes.len() <= pos {
+                nodes.resize(pos + 1, self.zeros[level]);
+            }
+            nodes[pos] = node;
+
+            if level < self.depth {
+                node = if pos & 1 == 0 {
+                    hasher.hash([node, self.node(level, pos + 1)])
+                } else {
+                    hasher.hash([self.node(level, pos - 1), node])
+                };
+            }
+        }
+    }
+
+    fn node(&self, level: usize, index: usize) -> F {
+        self.levels[level]
+            .get(index)
+            .copied()
+            .unwrap_or(self.zeros[level])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::pasta::Fp;
+
+    use super::MerkleTree;
+    use crate::primitives::{
+        poseidon::{ConstantLength, Hash},
+        HashSpec,
+    };
+
+    fn hash(left: Fp, right: Fp) -> Fp {
+        Hash::init(HashSpec, ConstantLength::<2>).hash([left, right])
+    }
+
+    #[test]
+    fn from_leaves_matches_sequential_hashing() {
+        let depth = 4;
+        let leaves: Vec<_> = (0..11).map(|i| Fp::from(i as u64 + 1)).collect();
+        let tree = MerkleTree::from_leaves(depth, &leaves);
+
+        let mut level = leaves.clone();
+        level.resize(1 << depth, Fp::zero());
+        while level.len() > 1 {
+            level = level.chunks(2).map(|pair| hash(pair[0], pair[1])).collect();
+        }
+        assert_eq!(tree.root(), level[0]);
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            let (path, position_bits) = tree.path(index);
+            let root = path
+                .iter()
+                .zip(position_bits.iter())
+                .fold(*leaf, |node, (sibling, bit)| {
+                    if *bit == Fp::zero() {
+                        hash(node, *sibling)
+                    } else {
+                        hash(*sibling, node)
+                    }
+                });
+            assert_eq!(root, tree.root());
+        }
+    }
+
+    #[test]
+    fn set_matches_from_leaves() {
+        let depth = 4;
+        let mut leaves: Vec<_> = (0..5).map(|i| Fp::from(i as u64 + 1)).collect();
+        let mut tree = MerkleTree::from_leaves(depth, &leaves);
+
+        for (index, leaf) in [(2, Fp::zero()), (9, Fp::from(42)), (15, Fp::from(7)), (0, Fp::from(8))] {
+            tree.set(index, leaf);
+            if leaves.len() <= index {
+                leaves.resize(index + 1, Fp::zero());
+            }
+            leaves[index] = leaf;
+            assert_eq!(tree.root(), MerkleTree::from_leaves(depth, &leaves).root());
+            assert_eq!(tree.path(3), MerkleTree::from_leaves(depth, &leaves).path(3));
+        }
+    }
+}
@@ -1,4 +1,3 @@
-use std::array;
 use std::fmt;
 use std::iter;
 use std::marker::PhantomData;
@@ -10,8 +9,17 @@ pub(crate) mod fq;
 pub(crate) mod grain;
 pub(crate) mod mds;
 
+mod generic;
+pub use generic::{P128Pow5T3Gen, Pow5Gen};
+
+mod optimized;
+pub use optimized::{permute_optimized, OptimizedConstants};
+
 mod p128pow5t3;
-pub use p128pow5t3::{P128Pow5T3};
+pub use p128pow5t3::P128Pow5T3;
+
+#[cfg(test)]
+pub(crate) mod test_vectors;
 
 use grain::SboxType;
 
@@ -24,6 +32,10 @@ pub(crate) type SpongeState<F, const RATE: usize> = [Option<F>; RATE];
 /// The type used to hold the MDS matrix and its inverse.
 pub(crate) type Mds<F, const T: usize> = [[F; T]; T];
 
+/// The type used to hold a domain's padding function.
+pub(crate) type PadAndAdd<F, const T: usize, const RATE: usize> =
+    Box<dyn Fn(&mut State<F, T>, &SpongeState<F, RATE>)>;
+
 /// A specification for a Poseidon permutation.
 pub trait Spec<F: FieldExt, const T: usize, const RATE: usize> {
     /// The number of full rounds for this specification.
@@ -40,9 +52,12 @@ pub trait Spec<F: FieldExt, const T: usize, const RATE: usize> {
     /// Side-loaded index of the first correct and secure MDS that will be generated by
     /// the reference implementation.
     ///
-    /// This is used by the default implementation of [`Spec::constants`]. If you are
-    /// hard-coding the constants, you may leave this unimplemented.
-    fn secure_mds(&self) -> usize;
+    /// This is only used by the default implementation of [`Spec::constants`], and
+    /// defaults to 0, the first matrix generated. Specifications that provide their own
+    /// constants need not implement it.
+    fn secure_mds(&self) -> usize {
+        0
+    }
 
     /// Generates `(round_constants, mds, mds^-1)` corresponding to this specification.
     fn constants(&self) -> (Vec<[F; T]>, Mds<F, T>, Mds<F, T>) {
@@ -68,6 +83,58 @@ pub trait Spec<F: FieldExt, const T: usize, const RATE: usize> {
 
         (round_constants, mds, mds_inv)
     }
+
+    /// Runs the permutation of this specification on the given state.
+    ///
+    /// The default implementation is the Poseidon permutation, [`permute`].
+    fn permute(state: &mut State<F, T>, mds: &Mds<F, T>, round_constants: &[[F; T]])
+    where
+        Self: Sized,
+    {
+        permute::<F, Self, T, RATE>(state, mds, round_constants)
+    }
+}
+
+/// The constants of a Poseidon specification, as returned by [`Spec::constants`].
+#[derive(Clone, Debug)]
+pub struct Constants<F, const T: usize> {
+    round_constants: Vec<[F; T]>,
+    mds: Mds<F, T>,
+    mds_inv: Mds<F, T>,
+}
+
+impl<F, const T: usize> Constants<F, T> {
+    /// The round constants, one row per round.
+    pub fn round_constants(&self) -> &[[F; T]] {
+        &self.round_constants
+    }
+
+    /// The MDS matrix.
+    pub fn mds(&self) -> &Mds<F, T> {
+        &self.mds
+    }
+
+    /// The inverse of the MDS matrix.
+    pub fn mds_inv(&self) -> &Mds<F, T> {
+        &self.mds_inv
+    }
+}
+
+impl<F, const T: usize> From<(Vec<[F; T]>, Mds<F, T>, Mds<F, T>)> for Constants<F, T> {
+    fn from((round_constants, mds, mds_inv): (Vec<[F; T]>, Mds<F, T>, Mds<F, T>)) -> Self {
+        Constants {
+            round_constants,
+            mds,
+            mds_inv,
+        }
+    }
+}
+
+/// A Poseidon specification whose constants are computed once and then shared by every
+/// [`PoseidonHasher`] for the lifetime of the program.
+pub trait CachedSpec<F: FieldExt, const T: usize, const RATE: usize>: Spec<F, T, RATE> {
+    /// Returns the cached constants for this specification.
+    fn cached_constants() -> &'static Constants<F, T>;
 }
 
 /// Runs the Poseidon permutation on the given state.
@@ -108,9 +175,9 @@ pub(crate) fn permute<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RA
     };
 
     iter::empty()
-        .chain(iter::repeat(&full_round as &dyn Fn(&mut State<F, T>, &[F; T])).take(r_f))
-        .chain(iter::repeat(&part_round as &dyn Fn(&mut State<F, T>, &[F; T])).take(r_p))
-        .chain(iter::repeat(&full_round as &dyn Fn(&mut State<F, T>, &[F; T])).take(r_f))
+        .chain(iter::repeat_n(&full_round as &dyn Fn(&mut State<F, T>, &[F; T]), r_f))
+        .chain(iter::repeat_n(&part_round as &dyn Fn(&mut State<F, T>, &[F; T]), r_p))
+        .chain(iter::repeat_n(&full_round as &dyn Fn(&mut State<F, T>, &[F; T]), r_f))
         .zip(round_constants.iter())
         .fold(state, |state, (round, rcs)| {
             round(state, rcs);
@@ -127,7 +194,7 @@ fn poseidon_duplex<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE:
 ) -> SpongeState<F, RATE> {
     pad_and_add(state, input);
 
-    permute::<F, S, T, RATE>(state, mds_matrix, round_constants);
+    S::permute(state, mds_matrix, round_constants);
 
     let mut output = [None; RATE];
     for (word, value) in output.iter_mut().zip(state.iter()) {
@@ -153,7 +220,7 @@ impl<F: Copy, const RATE: usize> Sponge<F, RATE> {
 pub(crate) struct Duplex<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize> {
     sponge: Sponge<F, RATE>,
     state: State<F, T>,
-    pad_and_add: Box<dyn Fn(&mut State<F, T>, &SpongeState<F, RATE>)>,
+    pad_and_add: PadAndAdd<F, T, RATE>,
     mds_matrix: Mds<F, T>,
     round_constants: Vec<[F; T]>,
     _marker: PhantomData<S>,
@@ -164,7 +231,7 @@ impl<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize> Duplex
     pub(crate) fn new(
         spec: S,
         initial_capacity_element: F,
-        pad_and_add: Box<dyn Fn(&mut State<F, T>, &SpongeState<F, RATE>)>,
+        pad_and_add: PadAndAdd<F, T, RATE>,
     ) -> Self {
         let (round_constants, mds_matrix, _) = spec.constants();
 
@@ -178,7 +245,7 @@ impl<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize> Duplex
             pad_and_add,
             mds_matrix,
             round_constants,
-            _marker: PhantomData::default(),
+            _marker: PhantomData,
         }
     }
 
@@ -243,12 +310,13 @@ pub trait Domain<F: FieldExt, const T: usize, const RATE: usize>: Copy + fmt::De
     /// The initial capacity element, encoding this domain.
     fn initial_capacity_element(&self) -> F;
 
-    /// The padding that will be added to each state word by [`Domain::pad_and_add`].
-    fn padding(&self) -> SpongeState<F, RATE>;
+    /// The padding that will be added to each state word by [`Domain::pad_and_add`],
+    /// for a duplex round that absorbs `input_len` words.
+    fn padding(&self, input_len: usize) -> SpongeState<F, RATE>;
 
     /// Returns a function that will update the given state with the given input to a
     /// duplex permutation round, applying padding according to this domain specification.
-    fn pad_and_add(&self) -> Box<dyn Fn(&mut State<F, T>, &SpongeState<F, RATE>)>;
+    fn pad_and_add(&self) -> PadAndAdd<F, T, RATE>;
 }
 
 /// A Poseidon hash function used with constant input length.
@@ -266,17 +334,17 @@ impl<F: FieldExt, const T: usize, const RATE: usize, const L: usize> Domain<F, T
         F::from_u128((L as u128) << 64)
     }
 
-    fn padding(&self) -> SpongeState<F, RATE> {
+    fn padding(&self, input_len: usize) -> SpongeState<F, RATE> {
         // For constant-input-length hashing, padding consists of the field elements being
-        // zero.
+        // zero, filling every word of the rate that is not taken by the input.
         let mut padding = [None; RATE];
-        for word in padding.iter_mut().skip(L) {
+        for word in padding.iter_mut().skip(input_len) {
             *word = Some(F::zero());
         }
         padding
     }
 
-    fn pad_and_add(&self) -> Box<dyn Fn(&mut State<F, T>, &SpongeState<F, RATE>)> {
+    fn pad_and_add(&self) -> PadAndAdd<F, T, RATE> {
         Box::new(|state, input| {
             // `Iterator::zip` short-circuits when one iterator completes, so this will only
             // mutate the rate portion of the state.
@@ -348,9 +416,171 @@ impl<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize, const
 {
     /// Hashes the given input.
     pub fn hash(mut self, message: [F; L]) -> F {
-        for value in array::IntoIter::new(message) {
+        for value in message {
             self.duplex.absorb(value);
         }
         self.duplex.squeeze()
     }
-}
\ No newline at end of file
+}
+
+/// A reusable Poseidon hasher backed by the cached constants of its specification.
+///
+/// Unlike [`Hash`], which derives the round constants and boxes a padding function
+/// every time it is initialized, a `PoseidonHasher` can hash any number of messages
+/// without allocating.
+pub struct PoseidonHasher<
+    F: FieldExt,
+    S: CachedSpec<F, T, RATE>,
+    D: Domain<F, T, RATE>,
+    const T: usize,
+    const RATE: usize,
+> {
+    constants: &'static Constants<F, T>,
+    domain: D,
+    _marker: PhantomData<S>,
+}
+
+impl<
+        F: FieldExt,
+        S: CachedSpec<F, T, RATE>,
+        D: Domain<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+    > fmt::Debug for PoseidonHasher<F, S, D, T, RATE>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoseidonHasher")
+            .field("width", &T)
+            .field("rate", &RATE)
+            .field("R_F", &S::full_rounds())
+            .field("R_P", &S::partial_rounds())
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+impl<
+        F: FieldExt,
+        S: CachedSpec<F, T, RATE>,
+        D: Domain<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+    > Clone for PoseidonHasher<F, S, D, T, RATE>
+{
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<
+        F: FieldExt,
+        S: CachedSpec<F, T, RATE>,
+        D: Domain<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+    > Copy for PoseidonHasher<F, S, D, T, RATE>
+{
+}
+
+impl<
+        F: FieldExt,
+        S: CachedSpec<F, T, RATE>,
+        D: Domain<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+    > PoseidonHasher<F, S, D, T, RATE>
+{
+    /// Initializes a new hasher for the given domain.
+    pub fn new(domain: D) -> Self {
+        PoseidonHasher {
+            constants: S::cached_constants(),
+            domain,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<
+        F: FieldExt,
+        S: CachedSpec<F, T, RATE>,
+        const T: usize,
+        const RATE: usize,
+        const L: usize,
+    > PoseidonHasher<F, S, ConstantLength<L>, T, RATE>
+{
+    /// Hashes the given input.
+    ///
+    /// This produces the same output as [`Hash::hash`] for the same specification.
+    pub fn hash(&self, message: [F; L]) -> F {
+        let mut state = [F::zero(); T];
+        state[RATE] = Domain::<F, T, RATE>::initial_capacity_element(&self.domain);
+
+        let mut permute_chunk = |chunk: &[F]| {
+            // For constant-input-length hashing, padding consists of the field elements
+            // being zero, so we only add the input words to the state.
+            for (word, value) in state.iter_mut().zip(chunk.iter()) {
+                *word += value;
+            }
+            S::permute(&mut state, &self.constants.mds, &self.constants.round_constants);
+        };
+
+        if L == 0 {
+            permute_chunk(&[]);
+        }
+        for chunk in message.chunks(RATE) {
+            permute_chunk(chunk);
+        }
+
+        state[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::arithmetic::FieldExt;
+    use pasta_curves::{pallas, vesta};
+
+    use super::{
+        test_vectors::{self, HashTestVector},
+        CachedSpec, ConstantLength, Hash, P128Pow5T3, PoseidonHasher,
+    };
+
+    #[test]
+    fn hasher_matches_hash() {
+        let hasher = PoseidonHasher::<pallas::Base, P128Pow5T3, _, 3, 2>::new(ConstantLength::<2>);
+        for i in 0..4u64 {
+            let message = [pallas::Base::from(i), pallas::Base::from(i + 7)];
+            assert_eq!(
+                hasher.hash(message),
+                Hash::init(P128Pow5T3, ConstantLength::<2>).hash(message),
+            );
+        }
+
+        let hasher = PoseidonHasher::<vesta::Base, P128Pow5T3, _, 3, 2>::new(ConstantLength::<3>);
+        let message = [vesta::Base::from(1), vesta::Base::from(2), vesta::Base::from(3)];
+        assert_eq!(
+            hasher.hash(message),
+            Hash::init(P128Pow5T3, ConstantLength::<3>).hash(message),
+        );
+    }
+
+    fn check_hash_test_vectors<F: FieldExt>(test_vectors: Vec<HashTestVector>)
+    where
+        P128Pow5T3: CachedSpec<F, 3, 2>,
+    {
+        let hasher = PoseidonHasher::<F, P128Pow5T3, _, 3, 2>::new(ConstantLength::<2>);
+        for tv in test_vectors {
+            let message = [
+                F::from_bytes(&tv.input[0]).unwrap(),
+                F::from_bytes(&tv.input[1]).unwrap(),
+            ];
+            assert_eq!(hasher.hash(message).to_bytes(), tv.output);
+        }
+    }
+
+    #[test]
+    fn hasher_test_vectors() {
+        check_hash_test_vectors::<pallas::Base>(test_vectors::fp::hash());
+        check_hash_test_vectors::<vesta::Base>(test_vectors::fq::hash());
+    }
+}
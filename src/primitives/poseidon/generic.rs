@@ -0,0 +1,96 @@
+use std::marker::PhantomData;
+
+use halo2::arithmetic::FieldExt;
+
+use super::Spec;
+
+/// A Poseidon specification using the $x^5$ S-box, with `R_F` full rounds and `R_P`
+/// partial rounds, whose constants are generated at runtime.
+///
+/// This implements [`Spec`] for every width `T` and rate `RATE < T`. The round constants
+/// and MDS matrix are derived with the Grain LFSR of the reference implementation, so
+/// the chosen parameters must be checked for security out-of-band, and
+/// [`Spec::secure_mds`] must be the number of insecure MDS matrices that the reference
+/// implementation rejects before finding a secure one.
+#[derive(Debug)]
+pub struct Pow5Gen<F: FieldExt, const R_F: usize, const R_P: usize> {
+    secure_mds: usize,
+    _field: PhantomData<F>,
+}
+
+impl<F: FieldExt, const R_F: usize, const R_P: usize> Pow5Gen<F, R_F, R_P> {
+    /// Constructs a specification that skips `secure_mds` MDS matrices before using one.
+    pub fn new(secure_mds: usize) -> Self {
+        Pow5Gen {
+            secure_mds,
+            _field: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt, const T: usize, const RATE: usize, const R_F: usize, const R_P: usize>
+    Spec<F, T, RATE> for Pow5Gen<F, R_F, R_P>
+{
+    fn full_rounds() -> usize {
+        R_F
+    }
+
+    fn partial_rounds() -> usize {
+        R_P
+    }
+
+    fn sbox(val: F) -> F {
+        val.pow_vartime([5])
+    }
+
+    fn secure_mds(&self) -> usize {
+        self.secure_mds
+    }
+}
+
+/// The same Poseidon specification as [`P128Pow5T3`], but constructed such that its
+/// constants will be generated at runtime.
+///
+/// [`P128Pow5T3`]: super::P128Pow5T3
+pub type P128Pow5T3Gen<F> = Pow5Gen<F, 8, 56>;
+
+#[cfg(test)]
+mod tests {
+    use pasta_curves::pallas;
+
+    use super::Pow5Gen;
+    use crate::primitives::poseidon::{permute, permute_optimized, OptimizedConstants, Spec};
+
+    #[test]
+    fn other_widths() {
+        // Poseidon-128 with a width of 5 uses R_F = 8 and R_P = 60.
+        type P128Pow5T5 = Pow5Gen<pallas::Base, 8, 60>;
+
+        let (round_constants, mds, mds_inv) = Spec::<_, 5, 4>::constants(&P128Pow5T5::new(0));
+        assert_eq!(round_constants.len(), 68);
+
+        // Verify that MDS * MDS^-1 = I.
+        #[allow(clippy::needless_range_loop)]
+        for i in 0..5 {
+            for j in 0..5 {
+                let expected = if i == j {
+                    pallas::Base::one()
+                } else {
+                    pallas::Base::zero()
+                };
+                assert_eq!(
+                    (0..5).fold(pallas::Base::zero(), |acc, k| acc + mds[i][k] * mds_inv[k][j]),
+                    expected
+                );
+            }
+        }
+
+        // The optimised permutation must agree with the dense one at this width too.
+        let mut state = [1, 2, 3, 4, 5].map(pallas::Base::from);
+        let mut expected = state;
+        permute::<_, P128Pow5T5, 5, 4>(&mut expected, &mds, &round_constants);
+        let constants = OptimizedConstants::new::<_, 4>(&P128Pow5T5::new(0));
+        permute_optimized::<_, P128Pow5T5, 5, 4>(&mut state, &constants);
+        assert_eq!(state, expected);
+    }
+}
@@ -0,0 +1,308 @@
+//! The optimised Poseidon permutation from appendix B of the Poseidon paper.
+//!
+//! The native [`permute`] multiplies the state by the dense MDS matrix in every round.
+//! In a partial round the S-box only touches the first state word, which allows two
+//! equivalent transformations of the round sequence:
+//!
+//! - Round constants of a partial round, other than the first word, can be moved through
+//!   the MDS matrix into the next round. Every partial round then adds a single scalar,
+//!   and the accumulated remainder is added by the first full round that follows.
+//! - Writing a matrix as `N = A · B`, where `B` leaves the first word untouched and `A`
+//!   is sparse, `B` commutes with the partial S-box and the scalar constant, so it can
+//!   be moved into the previous round. Working back from the last partial round, each
+//!   partial round is left with a sparse matrix and the final `B` is folded into the
+//!   MDS matrix of the last full round before the partial rounds.
+//!
+//! A sparse matrix multiplication costs `2T - 1` multiplications instead of `T^2`.
+//!
+//! [`permute`]: super::permute
+
+use halo2::arithmetic::FieldExt;
+
+use super::{Mds, Spec, State};
+
+/// A sparse matrix of the form
+///
+/// ```text
+/// [ m_00  row[0]  row[1]  ... ]
+/// [ col[0]   1      0     ... ]
+/// [ col[1]   0      1     ... ]
+/// [  ...                      ]
+/// ```
+///
+/// with `row` and `col` stored in the first `T - 1` entries of their arrays.
+#[derive(Clone, Copy, Debug)]
+struct SparseMatrix<F, const T: usize> {
+    m_00: F,
+    row: [F; T],
+    col: [F; T],
+}
+
+impl<F: FieldExt, const T: usize> SparseMatrix<F, T> {
+    fn apply(&self, state: &mut State<F, T>) {
+        let first = state[0];
+        state[0] = self.m_00 * first
+            + state[1..]
+                .iter()
+                .zip(self.row.iter())
+                .fold(F::zero(), |acc, (word, m)| acc + *m * word);
+        for (word, m) in state[1..].iter_mut().zip(self.col.iter()) {
+            *word += *m * first;
+        }
+    }
+}
+
+/// Pre-computed constants for [`permute_optimized`].
+#[derive(Clone, Debug)]
+pub struct OptimizedConstants<F, const T: usize> {
+    half_full_rounds: usize,
+    mds: Mds<F, T>,
+    // The MDS matrix of the last full round before the partial rounds.
+    pre_sparse_mds: Mds<F, T>,
+    // Round constants of the first R_F / 2 full rounds.
+    first_full_rcs: Vec<[F; T]>,
+    // Scalar round constants added to the first word in each partial round.
+    partial_rcs: Vec<F>,
+    sparse_matrices: Vec<SparseMatrix<F, T>>,
+    // Round constants of the last R_F / 2 full rounds, including the constants moved out
+    // of the partial rounds.
+    last_full_rcs: Vec<[F; T]>,
+}
+
+impl<F: FieldExt, const T: usize> OptimizedConstants<F, T> {
+    /// Derives the optimised constants from the constants of the given specification.
+    pub fn new<S: Spec<F, T, RATE>, const RATE: usize>(spec: &S) -> Self {
+        let (round_constants, mds, _) = spec.constants();
+        Self::from_constants::<S, RATE>(&round_constants, &mds)
+    }
+
+    /// Derives the optimised constants from the given round constants and MDS matrix.
+    pub(crate) fn from_constants<S: Spec<F, T, RATE>, const RATE: usize>(
+        round_constants: &[[F; T]],
+        mds: &Mds<F, T>,
+    ) -> Self {
+        let half_full_rounds = S::full_rounds() / 2;
+        let partial_rounds = S::partial_rounds();
+        assert!(partial_rounds > 0);
+        assert_eq!(round_constants.len(), 2 * half_full_rounds + partial_rounds);
+
+        let first_full_rcs = round_constants[..half_full_rounds].to_vec();
+        let mut last_full_rcs = round_constants[half_full_rounds + partial_rounds..].to_vec();
+
+        // Move every constant but the first out of each partial round and into the next
+        // round: M · (0, c_1, ..., c_{T-1}) is added by the round that follows.
+        let mut partial_rcs = Vec::with_capacity(partial_rounds);
+        let mut carry = [F::zero(); T];
+        for rcs in &round_constants[half_full_rounds..half_full_rounds + partial_rounds] {
+            let mut rcs = add(rcs, &carry);
+            partial_rcs.push(rcs[0]);
+            rcs[0] = F::zero();
+            carry = mul_vec(mds, &rcs);
+        }
+        if let Some(rcs) = last_full_rcs.first_mut() {
+            *rcs = add(rcs, &carry);
+        }
+
+        // Factor the partial-round matrices, starting from the last partial round and
+        // pushing the dense part of each factorisation into the previous round.
+        let mut sparse_matrices = Vec::with_capacity(partial_rounds);
+        let mut m = *mds;
+        for _ in 0..partial_rounds {
+            let (sparse, dense) = factorise(&m);
+            sparse_matrices.push(sparse);
+            m = mul(&dense, mds);
+        }
+        sparse_matrices.reverse();
+
+        OptimizedConstants {
+            half_full_rounds,
+            mds: *mds,
+            pre_sparse_mds: m,
+            first_full_rcs,
+            partial_rcs,
+            sparse_matrices,
+            last_full_rcs,
+        }
+    }
+}
+
+/// Runs the optimised Poseidon permutation on the given state.
+///
+/// This produces the same output as [`permute`] for the specification the constants
+/// were derived from.
+///
+/// [`permute`]: super::permute
+pub fn permute_optimized<F: FieldExt, S: Spec<F, T, RATE>, const T: usize, const RATE: usize>(
+    state: &mut State<F, T>,
+    constants: &OptimizedConstants<F, T>,
+) {
+    let full_round = |state: &mut State<F, T>, rcs: &[F; T], mds: &Mds<F, T>| {
+        for (word, rc) in state.iter_mut().zip(rcs.iter()) {
+            *word = S::sbox(*word + rc);
+        }
+        *state = mul_vec(mds, state);
+    };
+
+    for (r, rcs) in constants.first_full_rcs.iter().enumerate() {
+        let mds = if r + 1 == constants.half_full_rounds {
+            &constants.pre_sparse_mds
+        } else {
+            &constants.mds
+        };
+        full_round(state, rcs, mds);
+    }
+
+    for (rc, sparse) in constants
+        .partial_rcs
+        .iter()
+        .zip(constants.sparse_matrices.iter())
+    {
+        state[0] = S::sbox(state[0] + rc);
+        sparse.apply(state);
+    }
+
+    for rcs in constants.last_full_rcs.iter() {
+        full_round(state, rcs, &constants.mds);
+    }
+}
+
+/// Writes `m = A · B`, where `A` is sparse and `B` leaves the first word untouched.
+///
+/// With `m = [[m_00, r], [c, M']]`, `B = [[1, 0], [0, M']]` and
+/// `A = [[m_00, r · M'^-1], [c, I]]`.
+fn factorise<F: FieldExt, const T: usize>(m: &Mds<F, T>) -> (SparseMatrix<F, T>, Mds<F, T>) {
+    let mut dense = [[F::zero(); T]; T];
+    dense[0][0] = F::one();
+    for i in 1..T {
+        dense[i][1..].copy_from_slice(&m[i][1..]);
+    }
+
+    let minor: Vec<Vec<F>> = (1..T).map(|i| m[i][1..].to_vec()).collect();
+    let minor_inv = invert(minor);
+
+    let mut row = [F::zero(); T];
+    let mut col = [F::zero(); T];
+    for j in 0..T - 1 {
+        row[j] = (0..T - 1).fold(F::zero(), |acc, k| acc + m[0][k + 1] * minor_inv[k][j]);
+        col[j] = m[j + 1][0];
+    }
+
+    (
+        SparseMatrix {
+            m_00: m[0][0],
+            row,
+            col,
+        },
+        dense,
+    )
+}
+
+/// Inverts a square matrix by Gauss-Jordan elimination.
+///
+/// # Panics
+///
+/// Panics if the matrix is singular, which cannot happen for a minor of an MDS matrix.
+fn invert<F: FieldExt>(mut m: Vec<Vec<F>>) -> Vec<Vec<F>> {
+    let n = m.len();
+    let mut inv: Vec<Vec<F>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { F::one() } else { F::zero() }).collect())
+        .collect();
+
+    for col in 0..n {
+        let pivot = (col..n)
+            .find(|&row| !bool::from(m[row][col].is_zero()))
+            .expect("matrix is invertible");
+        m.swap(col, pivot);
+        inv.swap(col, pivot);
+
+        let scale = m[col][col].invert().unwrap();
+        for j in 0..n {
+            m[col][j] *= scale;
+            inv[col][j] *= scale;
+        }
+
+        for row in 0..n {
+            if row != col {
+                let factor = m[row][col];
+                for j in 0..n {
+                    let (m_col, inv_col) = (m[col][j], inv[col][j]);
+                    m[row][j] -= factor * m_col;
+                    inv[row][j] -= factor * inv_col;
+                }
+            }
+        }
+    }
+
+    inv
+}
+
+fn add<F: FieldExt, const T: usize>(a: &[F; T], b: &[F; T]) -> [F; T] {
+    let mut out = *a;
+    for (out, b) in out.iter_mut().zip(b.iter()) {
+        *out += b;
+    }
+    out
+}
+
+fn mul_vec<F: FieldExt, const T: usize>(m: &Mds<F, T>, v: &[F; T]) -> [F; T] {
+    let mut out = [F::zero(); T];
+    for (out, row) in out.iter_mut().zip(m.iter()) {
+        *out = row.iter().zip(v.iter()).fold(F::zero(), |acc, (m, v)| acc + *m * v);
+    }
+    out
+}
+
+fn mul<F: FieldExt, const T: usize>(a: &Mds<F, T>, b: &Mds<F, T>) -> Mds<F, T> {
+    let mut out = [[F::zero(); T]; T];
+    for i in 0..T {
+        for j in 0..T {
+            out[i][j] = (0..T).fold(F::zero(), |acc, k| acc + a[i][k] * b[k][j]);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::arithmetic::FieldExt;
+    use pasta_curves::{pallas, vesta};
+
+    use super::{permute_optimized, OptimizedConstants};
+    use crate::primitives::poseidon::{
+        permute,
+        test_vectors::{self, PermuteTestVector},
+        P128Pow5T3, Spec,
+    };
+
+    fn check_test_vectors<F: FieldExt>(test_vectors: Vec<PermuteTestVector>)
+    where
+        P128Pow5T3: Spec<F, 3, 2>,
+    {
+        let constants = OptimizedConstants::<F, 3>::new::<_, 2>(&P128Pow5T3);
+        let (round_constants, mds, _) = P128Pow5T3.constants();
+
+        for tv in test_vectors {
+            let initial_state = [
+                F::from_bytes(&tv.initial_state[0]).unwrap(),
+                F::from_bytes(&tv.initial_state[1]).unwrap(),
+                F::from_bytes(&tv.initial_state[2]).unwrap(),
+            ];
+
+            let mut state = initial_state;
+            permute_optimized::<F, P128Pow5T3, 3, 2>(&mut state, &constants);
+            for (expected, actual) in tv.final_state.iter().zip(state.iter()) {
+                assert_eq!(&actual.to_bytes(), expected);
+            }
+
+            let mut reference = initial_state;
+            permute::<F, P128Pow5T3, 3, 2>(&mut reference, &mds, &round_constants);
+            assert_eq!(state, reference);
+        }
+    }
+
+    #[test]
+    fn optimized_permute_test_vectors() {
+        check_test_vectors::<pallas::Base>(test_vectors::fp::permute());
+        check_test_vectors::<vesta::Base>(test_vectors::fq::permute());
+    }
+}
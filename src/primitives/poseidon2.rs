@@ -0,0 +1,316 @@
+//! The Poseidon2 permutation.
+//!
+//! Poseidon2 (<https://eprint.iacr.org/2023/323>) keeps the sponge construction and the
+//! round structure of Poseidon, but replaces the dense MDS matrix with a cheap external
+//! matrix $M_E$ in the full rounds and a cheap internal matrix $M_I$ in the partial
+//! rounds, applies $M_E$ once before the first round, and adds a single round constant
+//! in each partial round. The hashing API is shared with Poseidon by overriding
+//! [`Spec::permute`], so [`Hash`] and [`PoseidonHasher`] can be used unchanged.
+//!
+//! [`Hash`]: super::poseidon::Hash
+//! [`PoseidonHasher`]: super::poseidon::PoseidonHasher
+
+use halo2::arithmetic::{Field, FieldExt};
+use lazy_static::lazy_static;
+use pasta_curves::{pallas::Base as Fp, vesta::Base as Fq};
+
+use super::poseidon::{
+    grain::{Grain, SboxType},
+    CachedSpec, Constants, Mds, Spec, State,
+};
+
+/// Poseidon2 using the $x^5$ S-box, with a width of 3 field elements, and the standard
+/// number of rounds for 128-bit security: $R_F = 8, R_P = 56$.
+///
+/// The round constants are generated as in the Poseidon2 reference implementation,
+/// whose Pallas and Vesta instances for a width of 3 this matches.
+#[derive(Debug)]
+pub struct Poseidon2Pow5T3;
+
+/// The external matrix for a width of 3, `circ(2, 1, 1)`.
+pub(crate) fn external_matrix<F: FieldExt>() -> Mds<F, 3> {
+    let (one, two) = (F::one(), F::from_u64(2));
+    [[two, one, one], [one, two, one], [one, one, two]]
+}
+
+/// The internal matrix for a width of 3, `1 + diag(1, 1, 2)`.
+pub(crate) fn internal_matrix<F: FieldExt>() -> Mds<F, 3> {
+    let (one, two, three) = (F::one(), F::from_u64(2), F::from_u64(3));
+    [[two, one, one], [one, two, one], [one, one, three]]
+}
+
+/// Generates the round constants as the reference implementation does. The Grain LFSR
+/// of Poseidon yields one field element per S-box, `3 R_F + R_P` in all, and the row of
+/// each partial round holds its constant followed by zeros.
+fn round_constants<F: FieldExt, S: Spec<F, 3, 2>>() -> Vec<[F; 3]> {
+    let r_f = S::full_rounds();
+    let r_p = S::partial_rounds();
+    let mut grain = Grain::new(SboxType::Pow, 3, r_f as u16, r_p as u16);
+
+    let full_rounds = |grain: &mut Grain<F>, rounds| {
+        (0..rounds)
+            .map(|_| {
+                [
+                    grain.next_field_element(),
+                    grain.next_field_element(),
+                    grain.next_field_element(),
+                ]
+            })
+            .collect::<Vec<_>>()
+    };
+    let mut round_constants = full_rounds(&mut grain, r_f / 2);
+    round_constants.extend((0..r_p).map(|_| [grain.next_field_element(), F::zero(), F::zero()]));
+    round_constants.extend(full_rounds(&mut grain, r_f / 2));
+    round_constants
+}
+
+fn constants<F: FieldExt, S: Spec<F, 3, 2>>() -> (Vec<[F; 3]>, Mds<F, 3>, Mds<F, 3>) {
+    // circ(2, 1, 1)^-1 = circ(3, -1, -1) / 4.
+    let quarter = F::from_u64(4).invert().unwrap();
+    let (a, b) = (F::from_u64(3) * quarter, -quarter);
+    (
+        round_constants::<F, S>(),
+        external_matrix(),
+        [[a, b, b], [b, a, b], [b, b, a]],
+    )
+}
+
+/// Runs the Poseidon2 permutation with a width of 3 on the given state.
+fn permute<F: FieldExt, S: Spec<F, 3, 2>>(
+    state: &mut State<F, 3>,
+    external: &Mds<F, 3>,
+    round_constants: &[[F; 3]],
+) {
+    let r_f = S::full_rounds() / 2;
+    let r_p = S::partial_rounds();
+
+    let apply = |state: &mut State<F, 3>, m: &Mds<F, 3>| {
+        let mut new_state = [F::zero(); 3];
+        for (word, row) in new_state.iter_mut().zip(m.iter()) {
+            *word = row
+                .iter()
+                .zip(state.iter())
+                .fold(F::zero(), |acc, (m, s)| acc + *m * s);
+        }
+        *state = new_state;
+    };
+
+    let full_round = |state: &mut State<F, 3>, rcs: &[F; 3]| {
+        for (word, rc) in state.iter_mut().zip(rcs.iter()) {
+            *word = S::sbox(*word + rc);
+        }
+        apply(state, external);
+    };
+
+    let internal = internal_matrix();
+    let partial_round = |state: &mut State<F, 3>, rcs: &[F; 3]| {
+        state[0] = S::sbox(state[0] + rcs[0]);
+        apply(state, &internal);
+    };
+
+    apply(state, external);
+    for rcs in &round_constants[..r_f] {
+        full_round(state, rcs);
+    }
+    for rcs in &round_constants[r_f..r_f + r_p] {
+        partial_round(state, rcs);
+    }
+    for rcs in &round_constants[r_f + r_p..] {
+        full_round(state, rcs);
+    }
+}
+
+impl Spec<Fp, 3, 2> for Poseidon2Pow5T3 {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        56
+    }
+
+    fn sbox(val: Fp) -> Fp {
+        val.pow_vartime([5])
+    }
+
+    fn constants(&self) -> (Vec<[Fp; 3]>, Mds<Fp, 3>, Mds<Fp, 3>) {
+        constants::<Fp, Self>()
+    }
+
+    fn permute(state: &mut State<Fp, 3>, mds: &Mds<Fp, 3>, round_constants: &[[Fp; 3]]) {
+        permute::<Fp, Self>(state, mds, round_constants)
+    }
+}
+
+impl Spec<Fq, 3, 2> for Poseidon2Pow5T3 {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        56
+    }
+
+    fn sbox(val: Fq) -> Fq {
+        val.pow_vartime([5])
+    }
+
+    fn constants(&self) -> (Vec<[Fq; 3]>, Mds<Fq, 3>, Mds<Fq, 3>) {
+        constants::<Fq, Self>()
+    }
+
+    fn permute(state: &mut State<Fq, 3>, mds: &Mds<Fq, 3>, round_constants: &[[Fq; 3]]) {
+        permute::<Fq, Self>(state, mds, round_constants)
+    }
+}
+
+lazy_static! {
+    static ref FP_CONSTANTS: Constants<Fp, 3> =
+        Spec::<Fp, 3, 2>::constants(&Poseidon2Pow5T3).into();
+    static ref FQ_CONSTANTS: Constants<Fq, 3> =
+        Spec::<Fq, 3, 2>::constants(&Poseidon2Pow5T3).into();
+}
+
+impl CachedSpec<Fp, 3, 2> for Poseidon2Pow5T3 {
+    fn cached_constants() -> &'static Constants<Fp, 3> {
+        &FP_CONSTANTS
+    }
+}
+
+impl CachedSpec<Fq, 3, 2> for Poseidon2Pow5T3 {
+    fn cached_constants() -> &'static Constants<Fq, 3> {
+        &FQ_CONSTANTS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::arithmetic::FieldExt;
+    use pasta_curves::{pallas, vesta};
+
+    use super::{external_matrix, internal_matrix, Poseidon2Pow5T3};
+    use crate::primitives::poseidon::{
+        CachedSpec, ConstantLength, Hash, P128Pow5T3, PoseidonHasher, Spec,
+    };
+
+    fn check_matrices<F: FieldExt>()
+    where
+        Poseidon2Pow5T3: Spec<F, 3, 2>,
+    {
+        let (_, m_e, m_e_inv) = Poseidon2Pow5T3.constants();
+        assert_eq!(m_e, external_matrix());
+
+        #[allow(clippy::needless_range_loop)]
+        for i in 0..3 {
+            for j in 0..3 {
+                let expected = if i == j { F::one() } else { F::zero() };
+                assert_eq!(
+                    (0..3).fold(F::zero(), |acc, k| acc + m_e[i][k] * m_e_inv[k][j]),
+                    expected
+                );
+            }
+        }
+
+        // M_I must be invertible for the permutation to be a bijection.
+        let m_i = internal_matrix::<F>();
+        let det = m_i[0][0] * (m_i[1][1] * m_i[2][2] - m_i[1][2] * m_i[2][1])
+            - m_i[0][1] * (m_i[1][0] * m_i[2][2] - m_i[1][2] * m_i[2][0])
+            + m_i[0][2] * (m_i[1][0] * m_i[2][1] - m_i[1][1] * m_i[2][0]);
+        assert_ne!(det, F::zero());
+    }
+
+    fn check_hash<F: FieldExt>()
+    where
+        Poseidon2Pow5T3: Spec<F, 3, 2>,
+        P128Pow5T3: Spec<F, 3, 2>,
+        Poseidon2Pow5T3: CachedSpec<F, 3, 2>,
+    {
+        let message = [F::from_u64(1), F::from_u64(2)];
+        let output = Hash::init(Poseidon2Pow5T3, ConstantLength::<2>).hash(message);
+
+        let hasher = PoseidonHasher::<F, Poseidon2Pow5T3, _, 3, 2>::new(ConstantLength::<2>);
+        assert_eq!(hasher.hash(message), output);
+
+        assert_ne!(
+            output,
+            Hash::init(P128Pow5T3, ConstantLength::<2>).hash(message)
+        );
+    }
+
+    /// Parses a field element written in big-endian hex, as the reference does.
+    fn from_hex<F: FieldExt>(hex: &str) -> F {
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().rev().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        F::from_bytes(&bytes).unwrap()
+    }
+
+    /// Checks the round constants and the permutation of `[0, 1, 2]` against the
+    /// Poseidon2 reference implementation (`zkhash` 0.2.0).
+    fn check_reference<F: FieldExt>(expected: [&str; 3])
+    where
+        Poseidon2Pow5T3: Spec<F, 3, 2>,
+    {
+        let (round_constants, mds, _) = Poseidon2Pow5T3.constants();
+        assert_eq!(round_constants.len(), 64);
+        assert_eq!(
+            round_constants[0],
+            [
+                from_hex("360d7470611e473d353f628f76d110f34e71162f31003b7057538c2596426303"),
+                from_hex("2bab94d7ae222d135dc3c6c5febfaa314908ac2f12ebe06fbdb74213bf63188b"),
+                from_hex("150c93fef652fb1c2bf03e1a29aa871fef77e7d736766c5d0939d92753cc5dc8"),
+            ]
+        );
+        assert_eq!(
+            round_constants[4],
+            [
+                from_hex("1cbaf2b371dac6a81d0453416d3e235cb8d9e2d4f314f46f6198785f0cd6b9af"),
+                F::zero(),
+                F::zero(),
+            ]
+        );
+        assert_eq!(
+            round_constants[59],
+            [
+                from_hex("232f99cc911eddd9cd0f1fc55b1a3250092cb92119bc76be621a132510a43904"),
+                F::zero(),
+                F::zero(),
+            ]
+        );
+        assert_eq!(
+            round_constants[63],
+            [
+                from_hex("13a7785ae134ea92f1594a0763c611abb5e2ea3436eef957f1e4ccd73fa00a82"),
+                from_hex("39fce308b7d43c574962ae3c0da17e313889c57863446d88bbf04f5252de4279"),
+                from_hex("1aae18833f8e1d3ac0fdf01662f60d22bef00a08c6ed38d23b57e34489b53fad"),
+            ]
+        );
+
+        let mut state = [F::zero(), F::one(), F::from_u64(2)];
+        Poseidon2Pow5T3::permute(&mut state, &mds, &round_constants);
+        assert_eq!(state, expected.map(from_hex));
+    }
+
+    #[test]
+    fn reference_vectors() {
+        check_reference::<pallas::Base>([
+            "1a9b54c7512a914dd778282c44b3513fea7251420b9d95750baae059b2268d7a",
+            "1c48ea0994a7d7984ea338a54dbf0c8681f5af883fe988d59ba3380c9f7901fc",
+            "079ddd0a80a3e9414489b526a2770448964766685f4c4842c838f8a23120b401",
+        ]);
+        check_reference::<vesta::Base>([
+            "261ecbdfd62c617b82d297705f18c788fc9831b14a6a2b8f61229bef68ce2792",
+            "2c76327e0b7653873263158cf8545c282364b183880fcdea93ca8526d518c66f",
+            "262316c0ce5244838c75873299b59d763ae0849d2dd31bdc95caf7db1c2901bf",
+        ]);
+    }
+
+    #[test]
+    fn poseidon2() {
+        check_matrices::<pallas::Base>();
+        check_matrices::<vesta::Base>();
+        check_hash::<pallas::Base>();
+        check_hash::<vesta::Base>();
+    }
+}
@@ -0,0 +1,334 @@
+//! Serialized Semaphore proofs, tagged with the circuit that produced them.
+//!
+//! A [`CircuitId`] digests the verifying key, the tree depth and the hash of a circuit.
+//! [`prove`] embeds it in the [`SemaphoreProof`] it returns, and [`verify`] rejects
+//! proofs made for another circuit with [`ProofError::CircuitMismatch`] before running
+//! the verifier, so a client and a verifier that disagree on the circuit get a clear
+//! error instead of a failed verification.
+//!
+//! A serialized proof is laid out as
+//!
+//! | bytes  | field                                                   |
+//! |--------|---------------------------------------------------------|
+//! | 1      | format version, [`VERSION`]                             |
+//! | 32     | circuit id                                              |
+//! | 3 x 32 | external nullifier, nullifier hash and root             |
+//! | 4      | length of the halo2 proof, little endian                |
+//! | n      | halo2 proof, with a Blake2b transcript                  |
+
+use std::{fmt, io};
+
+use blake2b_simd::Params as Blake2bParams;
+use halo2::{
+    arithmetic::FieldExt,
+    pasta::{EqAffine, Fp},
+    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, Error, ProvingKey, VerifyingKey},
+    poly::commitment::Params,
+    transcript::{Blake2bRead, Blake2bWrite, Challenge255},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{dev::circuit_stats, primitives::HASH_SPEC_NAME, witness::PublicInputs, SemaphoreCircuit, MERKLE_DEPTH};
+
+/// The version of the serialization format.
+pub const VERSION: u8 = 1;
+
+/// Returns a Blake2b digest of the verifying key, as it is hashed into the transcript.
+pub fn vk_fingerprint(vk: &VerifyingKey<EqAffine>) -> [u8; 32] {
+    let pinned = format!("{:?}", vk.pinned());
+    let hash = Blake2bParams::new()
+        .hash_length(32)
+        .personal(b"Semaphore_VK_Fpr")
+        .hash(pinned.as_bytes());
+    let mut fingerprint = [0; 32];
+    fingerprint.copy_from_slice(hash.as_bytes());
+    fingerprint
+}
+
+/// Identifies the circuit a proof was made for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct CircuitId(pub [u8; 32]);
+
+impl fmt::Display for CircuitId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the identifier of [`SemaphoreCircuit`] for trees of depth `DEPTH` with the
+/// given verifying key, and the hash selected at compile time.
+pub fn circuit_id<const DEPTH: usize>(vk: &VerifyingKey<EqAffine>) -> CircuitId {
+    let mut state = Blake2bParams::new()
+        .hash_length(32)
+        .personal(b"Semaphore_Circui")
+        .to_state();
+    state.update(&(DEPTH as u64).to_le_bytes());
+    state.update(&(HASH_SPEC_NAME.len() as u64).to_le_bytes());
+    state.update(HASH_SPEC_NAME.as_bytes());
+    state.update(&vk_fingerprint(vk));
+
+    let mut id = [0; 32];
+    id.copy_from_slice(state.finalize().as_bytes());
+    CircuitId(id)
+}
+
+/// The reasons a proof is rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofError {
+    /// The bytes are not a serialized proof of a known version.
+    Malformed,
+    /// The proof was made for another circuit.
+    CircuitMismatch { expected: CircuitId, found: CircuitId },
+    /// The proof does not verify.
+    Invalid,
+}
+
+impl fmt::Display for ProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProofError::Malformed => write!(f, "malformed proof"),
+            ProofError::CircuitMismatch { expected, found } => write!(
+                f,
+                "proof is for circuit {}, expected circuit {}",
+                found, expected
+            ),
+            ProofError::Invalid => write!(f, "invalid proof"),
+        }
+    }
+}
+
+impl std::error::Error for ProofError {}
+
+/// A proof of a Semaphore signal, with its public inputs and circuit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SemaphoreProof {
+    pub circuit_id: CircuitId,
+    pub public_inputs: PublicInputs<Fp>,
+    pub proof: Vec<u8>,
+}
+
+impl SemaphoreProof {
+    /// Serializes the proof.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(1 + 32 * 4 + 4 + self.proof.len());
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.circuit_id.0);
+        for value in self.public_inputs.to_instance() {
+            bytes.extend_from_slice(&value.to_bytes());
+        }
+        bytes.extend_from_slice(&(self.proof.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.proof);
+        bytes
+    }
+
+    /// Deserializes a proof.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ProofError> {
+        let (version, bytes) = bytes.split_first().ok_or(ProofError::Malformed)?;
+        if *version != VERSION || bytes.len() < 32 * 4 + 4 {
+            return Err(ProofError::Malformed);
+        }
+        let (id, bytes) = bytes.split_at(32);
+        let (inputs, bytes) = bytes.split_at(32 * 3);
+        let (len, proof) = bytes.split_at(4);
+        if u32::from_le_bytes(len.try_into().unwrap()) as usize != proof.len() {
+            return Err(ProofError::Malformed);
+        }
+
+        let mut values = inputs.chunks(32).map(|chunk| {
+            Option::from(Fp::from_bytes(chunk.try_into().unwrap())).ok_or(ProofError::Malformed)
+        });
+        let mut next = || values.next().unwrap();
+        let public_inputs = PublicInputs {
+            external_nullifier: next()?,
+            nullifier_hash: next()?,
+            root: next()?,
+        };
+
+        Ok(SemaphoreProof {
+            circuit_id: CircuitId(id.try_into().unwrap()),
+            public_inputs,
+            proof: proof.to_vec(),
+        })
+    }
+}
+
+/// Returns `bytes` in lower case hexadecimal.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decodes hexadecimal, or returns `None` if `hex` is not an even number of hex digits.
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Writes the parameters and the verifying key of a circuit, which is all a verifier
+/// needs.
+pub fn write_verifying_key<W: io::Write>(
+    params: &Params<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    writer: &mut W,
+) -> io::Result<()> {
+    params.write(writer)?;
+    vk.write(writer)
+}
+
+/// Reads the parameters and the verifying key of [`SemaphoreCircuit`] for trees of
+/// depth `DEPTH`, as written by [`write_verifying_key`].
+///
+/// `VerifyingKey::read` does not compress the selectors as key generation does, so the
+/// key is generated again from the parameters and checked against the one read.
+pub fn read_verifying_key<const DEPTH: usize, R: io::Read>(
+    reader: &mut R,
+) -> io::Result<(Params<EqAffine>, VerifyingKey<EqAffine>)> {
+    let params = Params::read(reader)?;
+    let vk = keygen_vk(&params, &SemaphoreCircuit::<Fp, DEPTH>::default())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", error)))?;
+
+    let mut expected = vec![];
+    vk.write(&mut expected)?;
+    let mut found = vec![0; expected.len()];
+    reader.read_exact(&mut found)?;
+    if found != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the verifying key is for another circuit",
+        ));
+    }
+    Ok((params, vk))
+}
+
+/// Generates the parameters and proving key of [`SemaphoreCircuit`] for trees of depth
+/// `DEPTH`.
+pub fn keygen<const DEPTH: usize>() -> Result<(Params<EqAffine>, ProvingKey<EqAffine>), Error> {
+    let params = Params::new(circuit_stats::<DEPTH>().k);
+    let empty_circuit = SemaphoreCircuit::<Fp, DEPTH>::default();
+    let vk = keygen_vk(&params, &empty_circuit)?;
+    let pk = keygen_pk(&params, vk, &empty_circuit)?;
+    Ok((params, pk))
+}
+
+lazy_static! {
+    static ref KEYS: (Params<EqAffine>, ProvingKey<EqAffine>) =
+        keygen::<MERKLE_DEPTH>().expect("keygen failed");
+}
+
+/// Returns the parameters and proving key for trees of depth [`MERKLE_DEPTH`], which
+/// are generated on first use and shared by the language bindings.
+pub fn default_keys() -> &'static (Params<EqAffine>, ProvingKey<EqAffine>) {
+    &KEYS
+}
+
+/// Proves a signal with [`SemaphoreCircuit`] for trees of depth `DEPTH`.
+pub fn prove<const DEPTH: usize>(
+    params: &Params<EqAffine>,
+    pk: &ProvingKey<EqAffine>,
+    circuit: SemaphoreCircuit<Fp, DEPTH>,
+    public_inputs: PublicInputs<Fp>,
+) -> Result<SemaphoreProof, Error> {
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    create_proof(params, pk, &[circuit], &[&[&public_inputs.to_instance()]], &mut transcript)?;
+
+    Ok(SemaphoreProof {
+        circuit_id: circuit_id::<DEPTH>(pk.get_vk()),
+        public_inputs,
+        proof: transcript.finalize(),
+    })
+}
+
+/// Verifies a proof made with [`SemaphoreCircuit`] for trees of depth `DEPTH`.
+pub fn verify<const DEPTH: usize>(
+    params: &Params<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    proof: &SemaphoreProof,
+) -> Result<(), ProofError> {
+    let expected = circuit_id::<DEPTH>(vk);
+    if proof.circuit_id != expected {
+        return Err(ProofError::CircuitMismatch {
+            expected,
+            found: proof.circuit_id,
+        });
+    }
+
+    let instance = proof.public_inputs.to_instance();
+    let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof.proof[..]);
+    let guard = verify_proof(params, vk, params.empty_msm(), &[&[&instance]], &mut transcript)
+        .map_err(|_| ProofError::Invalid)?;
+    if guard.use_challenges().eval() {
+        Ok(())
+    } else {
+        Err(ProofError::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::pasta::Fp;
+
+    use super::{
+        circuit_id, from_hex, keygen, prove, read_verifying_key, to_hex, verify, write_verifying_key, ProofError,
+        SemaphoreProof,
+    };
+    use crate::{
+        fixtures::{Fixture, SEED},
+        MERKLE_DEPTH,
+    };
+
+    #[test]
+    fn round_trip_and_circuit_mismatch() {
+        let (params, pk) = keygen::<MERKLE_DEPTH>().unwrap();
+        let fixture = Fixture::<Fp, MERKLE_DEPTH>::new(SEED);
+        let proof = prove(&params, &pk, fixture.circuit, fixture.public_inputs).unwrap();
+
+        let bytes = proof.to_bytes();
+        let decoded = SemaphoreProof::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, proof);
+        assert_eq!(verify::<MERKLE_DEPTH>(&params, pk.get_vk(), &decoded), Ok(()));
+
+        assert_eq!(SemaphoreProof::from_bytes(&bytes[..bytes.len() - 1]), Err(ProofError::Malformed));
+        let mut future = bytes.clone();
+        future[0] += 1;
+        assert_eq!(SemaphoreProof::from_bytes(&future), Err(ProofError::Malformed));
+
+        // The same verifying key at another depth is another circuit.
+        let other = circuit_id::<{ MERKLE_DEPTH + 1 }>(pk.get_vk());
+        assert_ne!(other, proof.circuit_id);
+        assert_eq!(
+            verify::<{ MERKLE_DEPTH + 1 }>(&params, pk.get_vk(), &decoded),
+            Err(ProofError::CircuitMismatch {
+                expected: other,
+                found: proof.circuit_id,
+            })
+        );
+
+        let mut tampered = decoded;
+        tampered.public_inputs.root = Fp::zero();
+        assert_eq!(verify::<MERKLE_DEPTH>(&params, pk.get_vk(), &tampered), Err(ProofError::Invalid));
+    }
+
+    #[test]
+    fn verifying_key_round_trip() {
+        let (params, pk) = keygen::<MERKLE_DEPTH>().unwrap();
+        let mut bytes = vec![];
+        write_verifying_key(&params, pk.get_vk(), &mut bytes).unwrap();
+
+        let (_, vk) = read_verifying_key::<MERKLE_DEPTH, _>(&mut &bytes[..]).unwrap();
+        assert_eq!(circuit_id::<MERKLE_DEPTH>(&vk), circuit_id::<MERKLE_DEPTH>(pk.get_vk()));
+        assert!(read_verifying_key::<MERKLE_DEPTH, _>(&mut &bytes[..bytes.len() - 1]).is_err());
+        assert!(read_verifying_key::<{ MERKLE_DEPTH + 1 }, _>(&mut &bytes[..]).is_err());
+
+        assert_eq!(from_hex(&to_hex(&bytes[..40])).unwrap(), &bytes[..40]);
+        assert_eq!(from_hex("0g"), None);
+        assert_eq!(from_hex("abc"), None);
+    }
+}
@@ -0,0 +1,258 @@
+//! Verifying serialized proofs against a verifying key, with the checks a service adds.
+//!
+//! A [`Verifier`] owns the parameters and verifying key of [`SemaphoreCircuit`]. It can
+//! also restrict the roots that proofs are accepted against, and record the nullifier
+//! hashes of accepted proofs in a [`NullifierStore`], so that each member signals at most
+//! once per topic. A proof is only recorded once it has verified.
+//!
+//! [`SemaphoreCircuit`]: crate::SemaphoreCircuit
+
+use std::{collections::HashSet, fmt, sync::Mutex};
+
+use halo2::{
+    arithmetic::FieldExt,
+    pasta::{EqAffine, Fp},
+    plonk::VerifyingKey,
+    poly::commitment::Params,
+};
+
+use crate::{
+    batch::BatchVerifier,
+    nullifier::NullifierStore,
+    proof::{self, circuit_id, CircuitId, ProofError, SemaphoreProof},
+    MERKLE_DEPTH,
+};
+
+/// The reasons a [`Verifier`] rejects a proof.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerifyError {
+    Proof(ProofError),
+    /// The proof is against a root that is not allowed.
+    UnknownRoot,
+    /// The nullifier hash of the proof has already been used.
+    NullifierUsed,
+}
+
+impl From<ProofError> for VerifyError {
+    fn from(error: ProofError) -> Self {
+        VerifyError::Proof(error)
+    }
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Proof(error) => error.fmt(f),
+            VerifyError::UnknownRoot => write!(f, "unknown root"),
+            VerifyError::NullifierUsed => write!(f, "nullifier already used"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// The reason a batch of proofs is rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchError {
+    /// The index of the rejected proof, or `None` if the batch check failed as a whole.
+    pub index: Option<usize>,
+    pub error: VerifyError,
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "proof {}: {}", index, self.error),
+            None => write!(f, "batch: {}", self.error),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+/// Verifies proofs made with [`SemaphoreCircuit`] for trees of depth `DEPTH`.
+///
+/// [`SemaphoreCircuit`]: crate::SemaphoreCircuit
+#[derive(Debug)]
+pub struct Verifier<const DEPTH: usize = MERKLE_DEPTH> {
+    params: Params<EqAffine>,
+    vk: VerifyingKey<EqAffine>,
+    circuit_id: CircuitId,
+    roots: Option<HashSet<[u8; 32]>>,
+    nullifiers: Option<Mutex<NullifierStore>>,
+}
+
+impl<const DEPTH: usize> Verifier<DEPTH> {
+    /// Creates a verifier that accepts any valid proof for the circuit with the given
+    /// verifying key.
+    pub fn new(params: Params<EqAffine>, vk: VerifyingKey<EqAffine>) -> Self {
+        Verifier {
+            circuit_id: circuit_id::<DEPTH>(&vk),
+            params,
+            vk,
+            roots: None,
+            nullifiers: None,
+        }
+    }
+
+    /// Only accepts proofs against one of `roots`.
+    pub fn with_roots(mut self, roots: impl IntoIterator<Item = Fp>) -> Self {
+        self.roots = Some(roots.into_iter().map(|root| root.to_bytes()).collect());
+        self
+    }
+
+    /// Rejects proofs whose nullifier hash is in `store`, and records the nullifier hashes
+    /// of accepted proofs in it.
+    pub fn with_nullifier_store(mut self, store: NullifierStore) -> Self {
+        self.nullifiers = Some(Mutex::new(store));
+        self
+    }
+
+    /// Returns the identifier of the circuit the verifier accepts proofs for.
+    pub fn circuit_id(&self) -> CircuitId {
+        self.circuit_id
+    }
+
+    fn check_root(&self, proof: &SemaphoreProof) -> Result<(), VerifyError> {
+        match &self.roots {
+            Some(roots) if !roots.contains(&proof.public_inputs.root.to_bytes()) => Err(VerifyError::UnknownRoot),
+            _ => Ok(()),
+        }
+    }
+
+    /// Verifies a proof, and records its nullifier hash if the verifier has a store.
+    pub fn verify(&self, proof: &SemaphoreProof) -> Result<(), VerifyError> {
+        self.check_root(proof)?;
+        let nullifier_hash = proof.public_inputs.nullifier_hash;
+        if let Some(nullifiers) = &self.nullifiers {
+            if nullifiers.lock().unwrap().contains(nullifier_hash) {
+                return Err(VerifyError::NullifierUsed);
+            }
+        }
+
+        proof::verify::<DEPTH>(&self.params, &self.vk, proof)?;
+
+        // Another request may have used the nullifier hash while this proof was verified.
+        match &self.nullifiers {
+            Some(nullifiers) if !nullifiers.lock().unwrap().insert(nullifier_hash) => Err(VerifyError::NullifierUsed),
+            _ => Ok(()),
+        }
+    }
+
+    /// Verifies a batch of proofs with a [`BatchVerifier`], and returns the commitment to
+    /// their public inputs. The nullifier hashes are recorded only if every proof is valid,
+    /// and must be distinct within the batch.
+    pub fn verify_batch(&self, proofs: &[SemaphoreProof]) -> Result<Fp, BatchError> {
+        let at = |index| move |error: VerifyError| BatchError { index: Some(index), error };
+
+        let mut batch = BatchVerifier::new(&self.params, &self.vk);
+        let mut nullifier_hashes = HashSet::new();
+        for (index, proof) in proofs.iter().enumerate() {
+            if proof.circuit_id != self.circuit_id {
+                return Err(at(index)(
+                    ProofError::CircuitMismatch {
+                        expected: self.circuit_id,
+                        found: proof.circuit_id,
+                    }
+                    .into(),
+                ));
+            }
+            self.check_root(proof).map_err(at(index))?;
+            if self.nullifiers.is_some() && !nullifier_hashes.insert(proof.public_inputs.nullifier_hash.to_bytes()) {
+                return Err(at(index)(VerifyError::NullifierUsed));
+            }
+            batch
+                .add(proof.public_inputs, &proof.proof)
+                .map_err(|_| at(index)(ProofError::Invalid.into()))?;
+        }
+
+        let commitment = batch.finalize().map_err(|_| BatchError {
+            index: None,
+            error: ProofError::Invalid.into(),
+        })?;
+
+        if let Some(nullifiers) = &self.nullifiers {
+            let mut nullifiers = nullifiers.lock().unwrap();
+            if let Some(index) = proofs
+                .iter()
+                .position(|proof| nullifiers.contains(proof.public_inputs.nullifier_hash))
+            {
+                return Err(at(index)(VerifyError::NullifierUsed));
+            }
+            for proof in proofs {
+                nullifiers.insert(proof.public_inputs.nullifier_hash);
+            }
+        }
+        Ok(commitment)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::pasta::Fp;
+
+    use super::{BatchError, Verifier, VerifyError};
+    use crate::{
+        fixtures::{Fixture, SEED},
+        nullifier::NullifierStore,
+        proof::{keygen, prove, read_verifying_key, write_verifying_key, ProofError},
+        SemaphoreCircuit, MERKLE_DEPTH,
+    };
+
+    #[test]
+    fn roots_and_nullifiers() {
+        let (params, pk) = keygen::<MERKLE_DEPTH>().unwrap();
+        let fixture = Fixture::<Fp, MERKLE_DEPTH>::new(SEED);
+        let signal = |index: usize, topic: &[u8]| {
+            let (trapdoor, nullifier) = fixture.identities[index];
+            let (circuit, public_inputs) =
+                SemaphoreCircuit::<Fp, MERKLE_DEPTH>::from_group(trapdoor, nullifier, topic, &fixture.group, index)
+                    .unwrap();
+            prove(&params, &pk, circuit, public_inputs).unwrap()
+        };
+        let first = signal(0, b"topic");
+        let second = signal(1, b"topic");
+
+        let mut key = vec![];
+        write_verifying_key(&params, pk.get_vk(), &mut key).unwrap();
+        let (params, vk) = read_verifying_key::<MERKLE_DEPTH, _>(&mut &key[..]).unwrap();
+        let verifier = Verifier::<MERKLE_DEPTH>::new(params, vk)
+            .with_roots([fixture.group.root()])
+            .with_nullifier_store(NullifierStore::new());
+        assert_eq!(verifier.verify(&first), Ok(()));
+        assert_eq!(verifier.verify(&first), Err(VerifyError::NullifierUsed));
+
+        let mut tampered = second.clone();
+        tampered.public_inputs.root = Fp::zero();
+        assert_eq!(verifier.verify(&tampered), Err(VerifyError::UnknownRoot));
+
+        // The first proof is used, and fails the batch without recording the second.
+        assert_eq!(
+            verifier.verify_batch(&[second.clone(), first.clone()]),
+            Err(BatchError {
+                index: Some(1),
+                error: VerifyError::NullifierUsed,
+            })
+        );
+        assert_eq!(
+            verifier.verify_batch(&[second.clone(), second.clone()]),
+            Err(BatchError {
+                index: Some(1),
+                error: VerifyError::NullifierUsed,
+            })
+        );
+
+        // A well formed proof of another signal only fails the batch check.
+        let mut swapped = signal(2, b"topic");
+        swapped.proof = second.proof.clone();
+        assert_eq!(
+            verifier.verify_batch(&[second.clone(), swapped]),
+            Err(BatchError {
+                index: None,
+                error: VerifyError::Proof(ProofError::Invalid),
+            })
+        );
+
+        assert!(verifier.verify_batch(&[second]).is_ok());
+    }
+}
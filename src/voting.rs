@@ -0,0 +1,337 @@
+//! Anonymous polls on top of Semaphore.
+//!
+//! A vote is a Semaphore signal whose external nullifier is the poll id, so each member
+//! can vote once per poll. [`VotingCircuit`] additionally exposes the chosen option and
+//! the number of options, and checks that the option is one of them. Verified votes are
+//! counted with a [`Tally`].
+
+use std::{collections::BTreeSet, fmt};
+
+use halo2::{
+    arithmetic::FieldExt,
+    circuit::Layouter,
+    plonk::{Circuit, ConstraintSystem, Error, Expression, Selector},
+    poly::Rotation,
+};
+
+use crate::{
+    gadget::range_check::{RangeCheckChip, RangeCheckConfig, RangeCheckInstructions},
+    primitives::{
+        hash_to_field::external_nullifier, merkle::MerkleTree, poseidon::CachedSpec,
+        poseidon::Spec, HashSpec,
+    },
+    utils::{copy, CellValue, UtilitiesInstructions, Var},
+    witness::PublicInputs,
+    Config, SemaphoreCircuit, MERKLE_DEPTH,
+};
+
+/// The number of bits of a vote option. Polls have at most `2^VOTE_BITS` options.
+pub const VOTE_BITS: usize = 8;
+
+// Absolute offsets for the public inputs following those of `SemaphoreCircuit`.
+const VOTE: usize = 3;
+const NUM_OPTIONS: usize = 4;
+
+/// Configuration for a [`VotingCircuit`].
+#[derive(Clone, Debug)]
+pub struct VotingConfig<F: FieldExt> {
+    semaphore: Config<F>,
+    range_check: RangeCheckConfig,
+    s_option: Selector,
+}
+
+/// A Semaphore signal on a poll, whose signal is the index of the chosen option.
+///
+/// Its instance column holds the public inputs of [`SemaphoreCircuit`], followed by the
+/// vote and the number of options.
+#[derive(Clone, Debug, Default)]
+pub struct VotingCircuit<F: FieldExt, const DEPTH: usize = MERKLE_DEPTH> {
+    pub semaphore: SemaphoreCircuit<F, DEPTH>,
+    pub vote: Option<F>,
+    pub num_options: Option<F>,
+}
+
+impl<F: FieldExt, const DEPTH: usize> UtilitiesInstructions<F> for VotingCircuit<F, DEPTH> {
+    type Var = CellValue<F>;
+}
+
+impl<F: FieldExt, const DEPTH: usize> Circuit<F> for VotingCircuit<F, DEPTH>
+where
+    HashSpec: Spec<F, 3, 2>,
+{
+    type Config = VotingConfig<F>;
+    type FloorPlanner = <SemaphoreCircuit<F, DEPTH> as Circuit<F>>::FloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let semaphore = SemaphoreCircuit::<F, DEPTH>::configure(meta);
+        let advices = semaphore.advices;
+
+        let range_check = RangeCheckChip::<F, VOTE_BITS>::configure(meta, advices[3]);
+
+        // vote + rest + 1 = num_options: with both vote and rest in [0, 2^VOTE_BITS), the
+        // vote is below the number of options.
+        let s_option = meta.selector();
+        meta.create_gate("vote option", |meta| {
+            let s_option = meta.query_selector(s_option);
+            let vote = meta.query_advice(advices[0], Rotation::cur());
+            let rest = meta.query_advice(advices[1], Rotation::cur());
+            let num_options = meta.query_advice(advices[2], Rotation::cur());
+            vec![s_option * (vote + rest + Expression::Constant(F::one()) - num_options)]
+        });
+
+        VotingConfig {
+            semaphore,
+            range_check,
+            s_option,
+        }
+    }
+
+    fn synthesize(&self, config: Self::Config, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        let advices = config.semaphore.advices;
+        let instance = config.semaphore.instance;
+
+        self.semaphore
+            .synthesize(config.semaphore, layouter.namespace(|| "semaphore"))?;
+
+        let vote = self.load_private(layouter.namespace(|| "witness vote"), advices[0], self.vote)?;
+        let num_options = self.load_private(
+            layouter.namespace(|| "witness num_options"),
+            advices[0],
+            self.num_options,
+        )?;
+
+        let rest = layouter.assign_region(
+            || "vote option",
+            |mut region| {
+                config.s_option.enable(&mut region, 0)?;
+                copy(&mut region, || "vote", advices[0], 0, &vote)?;
+                copy(&mut region, || "num_options", advices[2], 0, &num_options)?;
+
+                let value = vote
+                    .value()
+                    .zip(num_options.value())
+                    .map(|(vote, num_options)| num_options - vote - F::one());
+                let cell = region.assign_advice(
+                    || "rest",
+                    advices[1],
+                    0,
+                    || value.ok_or(Error::SynthesisError),
+                )?;
+                Ok(CellValue::new(cell, value))
+            },
+        )?;
+
+        let range_check = RangeCheckChip::<F, VOTE_BITS>::construct(config.range_check);
+        range_check.range_check(layouter.namespace(|| "range check vote"), vote)?;
+        range_check.range_check(layouter.namespace(|| "range check rest"), rest)?;
+
+        self.expose_public(layouter.namespace(|| "constrain vote"), instance, vote, VOTE)?;
+        self.expose_public(layouter.namespace(|| "constrain num_options"), instance, num_options, NUM_OPTIONS)
+    }
+}
+
+/// The public inputs of [`VotingCircuit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VotePublicInputs<F: FieldExt> {
+    pub semaphore: PublicInputs<F>,
+    pub vote: u64,
+    pub num_options: u64,
+}
+
+impl<F: FieldExt> VotePublicInputs<F> {
+    /// Returns the instance column expected by [`VotingCircuit`].
+    pub fn to_instance(&self) -> Vec<F> {
+        let mut instance = self.semaphore.to_instance();
+        instance.push(F::from_u64(self.vote));
+        instance.push(F::from_u64(self.num_options));
+        instance
+    }
+}
+
+impl<F: FieldExt, const DEPTH: usize> VotingCircuit<F, DEPTH>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Builds the witness for the identity at `index` in `tree` voting for option `vote`
+    /// of `num_options` in the poll `poll_id`, together with the matching public inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the vote is not one of the options, if there are more than
+    /// `2^VOTE_BITS` options, or under the conditions of [`SemaphoreCircuit::from_tree`].
+    pub fn from_tree(
+        identity_trapdoor: F,
+        identity_nullifier: F,
+        poll_id: &[u8],
+        tree: &MerkleTree<F>,
+        index: usize,
+        vote: u64,
+        num_options: u64,
+    ) -> (Self, VotePublicInputs<F>) {
+        assert!(num_options <= 1 << VOTE_BITS, "too many options");
+        assert!(vote < num_options, "vote {} is not one of the options", vote);
+
+        let (semaphore, public_inputs) =
+            SemaphoreCircuit::from_tree(identity_trapdoor, identity_nullifier, poll_id, tree, index);
+        let circuit = VotingCircuit {
+            semaphore,
+            vote: Some(F::from_u64(vote)),
+            num_options: Some(F::from_u64(num_options)),
+        };
+        let public_inputs = VotePublicInputs {
+            semaphore: public_inputs,
+            vote,
+            num_options,
+        };
+
+        (circuit, public_inputs)
+    }
+}
+
+/// The reasons a [`Tally`] rejects a vote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TallyError {
+    /// The vote was cast on another poll or against another group.
+    WrongPoll,
+    /// The vote is not one of the poll's options.
+    InvalidOption,
+    /// A vote with the same nullifier hash has already been counted.
+    DuplicateNullifier,
+}
+
+impl fmt::Display for TallyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TallyError::WrongPoll => write!(f, "vote was cast on another poll"),
+            TallyError::InvalidOption => write!(f, "vote is not one of the options"),
+            TallyError::DuplicateNullifier => write!(f, "nullifier has already voted"),
+        }
+    }
+}
+
+impl std::error::Error for TallyError {}
+
+/// Counts the votes on a poll.
+///
+/// Votes are counted from their public inputs, so only votes whose proofs have been
+/// verified should be added.
+#[derive(Clone, Debug)]
+pub struct Tally<F: FieldExt> {
+    external_nullifier: F,
+    root: F,
+    counts: Vec<u64>,
+    nullifiers: BTreeSet<F>,
+}
+
+impl<F: FieldExt> Tally<F> {
+    /// Starts the tally of the poll `poll_id` among the group with the given root.
+    pub fn new(poll_id: &[u8], root: F, num_options: u64) -> Self {
+        Tally {
+            external_nullifier: external_nullifier(poll_id),
+            root,
+            counts: vec![0; num_options as usize],
+            nullifiers: BTreeSet::new(),
+        }
+    }
+
+    /// Counts a verified vote.
+    pub fn add(&mut self, vote: &VotePublicInputs<F>) -> Result<(), TallyError> {
+        if vote.semaphore.external_nullifier != self.external_nullifier
+            || vote.semaphore.root != self.root
+            || vote.num_options != self.counts.len() as u64
+        {
+            return Err(TallyError::WrongPoll);
+        }
+        let count = self
+            .counts
+            .get_mut(vote.vote as usize)
+            .ok_or(TallyError::InvalidOption)?;
+        if !self.nullifiers.insert(vote.semaphore.nullifier_hash) {
+            return Err(TallyError::DuplicateNullifier);
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Returns the number of votes for each option.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{dev::MockProver, pasta::Fp};
+
+    use super::{Tally, TallyError, VotingCircuit};
+    use crate::{primitives::merkle::MerkleTree, witness::identity_commitment, MERKLE_DEPTH};
+
+    fn identities() -> Vec<(Fp, Fp)> {
+        (0..3).map(|i| (Fp::from(10 + i), Fp::from(20 + i))).collect()
+    }
+
+    fn group() -> MerkleTree<Fp> {
+        let leaves: Vec<_> = identities()
+            .into_iter()
+            .map(|(trapdoor, nullifier)| identity_commitment(trapdoor, nullifier))
+            .collect();
+        MerkleTree::from_leaves(MERKLE_DEPTH, &leaves)
+    }
+
+    #[test]
+    fn vote_option_is_range_checked() {
+        let tree = group();
+        let (trapdoor, nullifier) = identities()[1];
+        let (circuit, public_inputs) =
+            VotingCircuit::<Fp>::from_tree(trapdoor, nullifier, b"poll", &tree, 1, 2, 3);
+
+        let prover = MockProver::run(10, &circuit, vec![public_inputs.to_instance()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // Claiming the last option as a vote for another one fails.
+        let mut other = public_inputs;
+        other.vote = 1;
+        let prover = MockProver::run(10, &circuit, vec![other.to_instance()]).unwrap();
+        assert!(prover.verify().is_err());
+
+        // A vote past the last option fails the range check.
+        for vote in [3, 200] {
+            let circuit = VotingCircuit {
+                vote: Some(Fp::from(vote)),
+                ..circuit.clone()
+            };
+            let mut public_inputs = public_inputs;
+            public_inputs.vote = vote;
+            let prover = MockProver::run(10, &circuit, vec![public_inputs.to_instance()]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
+
+    #[test]
+    fn tally() {
+        let tree = group();
+        let mut tally = Tally::new(b"poll", tree.root(), 3);
+
+        let votes: Vec<_> = identities()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (trapdoor, nullifier))| {
+                VotingCircuit::<Fp>::from_tree(trapdoor, nullifier, b"poll", &tree, index, 0, 3).1
+            })
+            .collect();
+        for vote in &votes {
+            tally.add(vote).unwrap();
+        }
+        assert_eq!(tally.add(&votes[0]), Err(TallyError::DuplicateNullifier));
+        assert_eq!(tally.counts(), &[3, 0, 0]);
+
+        let (trapdoor, nullifier) = identities()[0];
+        let (_, other_poll) =
+            VotingCircuit::<Fp>::from_tree(trapdoor, nullifier, b"other poll", &tree, 0, 1, 3);
+        assert_eq!(tally.add(&other_poll), Err(TallyError::WrongPoll));
+    }
+}
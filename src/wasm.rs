@@ -0,0 +1,170 @@
+//! Bindings for proving and verifying in the browser.
+//!
+//! The functions work on byte arrays, as the [C ABI](crate::ffi) does: field elements
+//! are 32 bytes, little endian, an identity is its trapdoor followed by its nullifier,
+//! and a proof is a serialized [`SemaphoreProof`]. Errors are returned as messages,
+//! which JavaScript receives as exceptions. Proofs are made for trees of depth
+//! [`MERKLE_DEPTH`], with keys generated on first use.
+//!
+//! With the `wasm` feature the functions are exported with `wasm-bindgen`; without it
+//! they are plain Rust functions, so the same code is tested natively. `cargo build-wasm`
+//! builds the module for `wasm32-unknown-unknown`.
+//!
+//! The bindings only take and return owned bytes, and use neither the clock nor the
+//! filesystem. The prover and the tree parallelize with `rayon`, which the feature
+//! requires at 1.11 or later, so that its pool runs on the calling thread where threads
+//! cannot be spawned. The rest of the crate needs `std`, as `halo2` 0.1.0-beta.1 does.
+
+use halo2::{arithmetic::FieldExt, pasta::Fp};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{
+    group::GroupError,
+    primitives::merkle::{empty_leaf, MerkleTree},
+    proof::{self, default_keys, SemaphoreProof},
+    witness::{identity_commitment, identity_from_secret, PublicInputs},
+    SemaphoreCircuit, MERKLE_DEPTH,
+};
+
+fn field_elements(bytes: &[u8]) -> Result<Vec<Fp>, String> {
+    if !bytes.len().is_multiple_of(32) {
+        return Err("length is not a multiple of 32 bytes".to_string());
+    }
+    bytes
+        .chunks(32)
+        .map(|chunk| {
+            Option::from(Fp::from_bytes(chunk.try_into().unwrap()))
+                .ok_or_else(|| "non-canonical field element".to_string())
+        })
+        .collect()
+}
+
+fn identity(bytes: &[u8]) -> Result<(Fp, Fp), String> {
+    match field_elements(bytes)?[..] {
+        [trapdoor, nullifier] => Ok((trapdoor, nullifier)),
+        _ => Err("an identity is 64 bytes".to_string()),
+    }
+}
+
+/// Derives an identity from `secret`, and returns its 64 bytes.
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = identityNew))]
+pub fn identity_new(secret: &[u8]) -> Vec<u8> {
+    let (trapdoor, nullifier): (Fp, Fp) = identity_from_secret(secret);
+    [trapdoor.to_bytes(), nullifier.to_bytes()].concat()
+}
+
+/// Returns the 32-byte identity commitment of the 64-byte `identity`.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn commitment(identity: &[u8]) -> Result<Vec<u8>, String> {
+    let (trapdoor, nullifier) = self::identity(identity)?;
+    Ok(identity_commitment(trapdoor, nullifier).to_bytes().to_vec())
+}
+
+/// The private and public inputs of a signal, ready to be proved.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Clone, Debug)]
+pub struct Witness {
+    circuit: SemaphoreCircuit<Fp, MERKLE_DEPTH>,
+    public_inputs: PublicInputs<Fp>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl Witness {
+    /// Returns the external nullifier, nullifier hash and root, 32 bytes each.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter, js_name = publicInputs))]
+    pub fn public_inputs(&self) -> Vec<u8> {
+        self.public_inputs
+            .to_instance()
+            .iter()
+            .flat_map(|value| value.to_bytes())
+            .collect()
+    }
+}
+
+/// Builds the witness proving that `identity` is the member at `index` of the group
+/// whose commitments are `leaves`, 32 bytes each, and signals on `topic`.
+///
+/// The leaves of removed members are zero, the empty leaf, and no identity can prove at
+/// their index.
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = buildWitness))]
+pub fn build_witness(identity: &[u8], leaves: &[u8], index: usize, topic: &[u8]) -> Result<Witness, String> {
+    let (trapdoor, nullifier) = self::identity(identity)?;
+    let leaves = field_elements(leaves)?;
+    if leaves.len() > 1 << MERKLE_DEPTH {
+        return Err(GroupError::Full.to_string());
+    }
+    match leaves.get(index) {
+        None => return Err(GroupError::NoSuchMember(index).to_string()),
+        Some(&leaf) if leaf == empty_leaf() => return Err(GroupError::Removed(index).to_string()),
+        Some(&leaf) if leaf != identity_commitment(trapdoor, nullifier) => {
+            return Err(GroupError::NotMember(index).to_string())
+        }
+        Some(_) => {}
+    }
+
+    let tree = MerkleTree::from_leaves(MERKLE_DEPTH, &leaves);
+    let (circuit, public_inputs) =
+        SemaphoreCircuit::<Fp, MERKLE_DEPTH>::from_tree(trapdoor, nullifier, topic, &tree, index);
+    Ok(Witness { circuit, public_inputs })
+}
+
+/// Proves the signal of `witness`, and returns the serialized proof.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn prove(witness: &Witness) -> Result<Vec<u8>, String> {
+    let (params, pk) = default_keys();
+    proof::prove(params, pk, witness.circuit.clone(), witness.public_inputs)
+        .map(|proof| proof.to_bytes())
+        .map_err(|error| format!("prover failed: {:?}", error))
+}
+
+/// Verifies a serialized proof.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub fn verify(proof: &[u8]) -> Result<(), String> {
+    let (params, pk) = default_keys();
+    SemaphoreProof::from_bytes(proof)
+        .and_then(|proof| proof::verify::<MERKLE_DEPTH>(params, pk.get_vk(), &proof))
+        .map_err(|error| error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_witness, commitment, identity_new, prove, verify};
+
+    #[test]
+    fn prove_and_verify() {
+        let identities: Vec<_> = (0..3u8).map(|i| identity_new(&[i; 16])).collect();
+        let leaves: Vec<u8> = identities.iter().flat_map(|identity| commitment(identity).unwrap()).collect();
+        assert!(commitment(&identities[0][..32]).is_err());
+
+        let witness = build_witness(&identities[1], &leaves, 1, b"topic").unwrap();
+        assert_eq!(witness.public_inputs().len(), 3 * 32);
+        assert_eq!(
+            build_witness(&identities[1], &leaves, 2, b"topic").unwrap_err(),
+            "member 2 is another identity"
+        );
+
+        let mut proof = prove(&witness).unwrap();
+        assert_eq!(verify(&proof), Ok(()));
+        assert_eq!(proof[1 + 32..1 + 32 * 4], witness.public_inputs()[..]);
+
+        // Once the first member is removed, its leaf is zero and the others can still prove.
+        let mut removed = leaves.clone();
+        removed[..32].fill(0);
+        let witness = build_witness(&identities[2], &removed, 2, b"topic").unwrap();
+        assert_eq!(verify(&prove(&witness).unwrap()), Ok(()));
+        assert_eq!(
+            build_witness(&identities[0], &removed, 0, b"topic").unwrap_err(),
+            "member 0 has been removed"
+        );
+        assert_eq!(
+            build_witness(&identities[0], &removed, 3, b"topic").unwrap_err(),
+            "no member at index 3"
+        );
+
+        // A proof does not verify with another external nullifier.
+        proof[1 + 32] ^= 1;
+        assert_eq!(verify(&proof), Err("invalid proof".to_string()));
+        assert_eq!(verify(&proof[1..]), Err("malformed proof".to_string()));
+    }
+}
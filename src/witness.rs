@@ -0,0 +1,195 @@
+//! Native helpers for building [`SemaphoreCircuit`] witnesses and the public inputs a
+//! verifier checks them against.
+//!
+//! Topics are mapped to external nullifiers with [`external_nullifier`] on both sides,
+//! so a prover and a verifier that agree on the topic bytes agree on the public input.
+
+use halo2::arithmetic::FieldExt;
+
+use crate::{
+    primitives::{
+        hash_to_field::{
+            external_nullifier, hash_bytes_to_field, IDENTITY_NULLIFIER_PERSONALIZATION,
+            IDENTITY_TRAPDOOR_PERSONALIZATION,
+        },
+        merkle::MerkleTree,
+        poseidon::{CachedSpec, ConstantLength, PoseidonHasher},
+        HashSpec,
+    },
+    SemaphoreCircuit, EXTERNAL_NULLIFIER, NULLIFIER_HASH, ROOT,
+};
+
+pub(crate) fn hash<F: FieldExt>(message: [F; 2]) -> F
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    PoseidonHasher::<F, HashSpec, _, 3, 2>::new(ConstantLength::<2>).hash(message)
+}
+
+/// Derives an identity trapdoor and nullifier from a secret, such as a signature by a
+/// key the member already holds.
+pub fn identity_from_secret<F: FieldExt>(secret: &[u8]) -> (F, F) {
+    (
+        hash_bytes_to_field(IDENTITY_TRAPDOOR_PERSONALIZATION, secret),
+        hash_bytes_to_field(IDENTITY_NULLIFIER_PERSONALIZATION, secret),
+    )
+}
+
+/// Returns the identity commitment inserted into the group tree for an identity.
+pub fn identity_commitment<F: FieldExt>(identity_trapdoor: F, identity_nullifier: F) -> F
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    hash([identity_trapdoor, identity_nullifier])
+}
+
+/// Returns the nullifier hash an identity reveals when signalling on a topic.
+pub fn nullifier_hash<F: FieldExt>(identity_nullifier: F, external_nullifier: F) -> F
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    hash([identity_nullifier, external_nullifier])
+}
+
+/// Returns the Merkle path and position bits proving that `commitment` is the leaf at
+/// `index` in `tree`.
+///
+/// # Panics
+///
+/// Panics if `tree` is not of depth `DEPTH`, or if the leaf at `index` is not
+/// `commitment`.
+pub(crate) fn merkle_witness<F: FieldExt, const DEPTH: usize>(
+    commitment: F,
+    tree: &MerkleTree<F>,
+    index: usize,
+) -> ([F; DEPTH], [F; DEPTH])
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    assert_eq!(tree.depth(), DEPTH, "tree depth must match the circuit");
+
+    let (path, position_bits) = tree.path(index);
+    let root = path
+        .iter()
+        .zip(position_bits.iter())
+        .fold(commitment, |node, (sibling, bit)| {
+            if *bit == F::zero() {
+                hash([node, *sibling])
+            } else {
+                hash([*sibling, node])
+            }
+        });
+    assert_eq!(root, tree.root(), "leaf {} is not this identity", index);
+
+    (path.try_into().unwrap(), position_bits.try_into().unwrap())
+}
+
+/// The public inputs of [`SemaphoreCircuit`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PublicInputs<F: FieldExt> {
+    pub external_nullifier: F,
+    pub nullifier_hash: F,
+    pub root: F,
+}
+
+impl<F: FieldExt> PublicInputs<F> {
+    /// Returns the public inputs for a signal on `topic` with the given nullifier hash,
+    /// against the group tree with the given root.
+    pub fn new(topic: &[u8], nullifier_hash: F, root: F) -> Self {
+        PublicInputs {
+            external_nullifier: external_nullifier(topic),
+            nullifier_hash,
+            root,
+        }
+    }
+
+    /// Returns the instance column expected by [`SemaphoreCircuit`].
+    pub fn to_instance(&self) -> Vec<F> {
+        let mut instance = vec![F::zero(); 3];
+        instance[EXTERNAL_NULLIFIER] = self.external_nullifier;
+        instance[NULLIFIER_HASH] = self.nullifier_hash;
+        instance[ROOT] = self.root;
+        instance
+    }
+}
+
+impl<F: FieldExt, const DEPTH: usize> SemaphoreCircuit<F, DEPTH>
+where
+    HashSpec: CachedSpec<F, 3, 2>,
+{
+    /// Builds the witness proving that the identity at `index` in `tree` signals on
+    /// `topic`, together with the matching public inputs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tree` is not of depth `DEPTH`, or if the leaf at `index` is not the
+    /// commitment to the given identity.
+    pub fn from_tree(
+        identity_trapdoor: F,
+        identity_nullifier: F,
+        topic: &[u8],
+        tree: &MerkleTree<F>,
+        index: usize,
+    ) -> (Self, PublicInputs<F>) {
+        let commitment = identity_commitment(identity_trapdoor, identity_nullifier);
+        let (path, position_bits) = merkle_witness(commitment, tree, index);
+        let root = tree.root();
+
+        let public_inputs = PublicInputs::new(
+            topic,
+            nullifier_hash(identity_nullifier, external_nullifier(topic)),
+            root,
+        );
+        let circuit = SemaphoreCircuit {
+            identity_trapdoor: Some(identity_trapdoor),
+            identity_nullifier: Some(identity_nullifier),
+            external_nullifier: Some(public_inputs.external_nullifier),
+            position_bits: Some(position_bits),
+            path: Some(path),
+            root: Some(root),
+        };
+
+        (circuit, public_inputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use halo2::{arithmetic::FieldExt, dev::MockProver};
+    use pasta_curves::{pallas, vesta};
+
+    use super::{identity_commitment, PublicInputs};
+    use crate::{
+        primitives::{merkle::MerkleTree, poseidon::CachedSpec, HashSpec},
+        SemaphoreCircuit, MERKLE_DEPTH,
+    };
+
+    fn check_from_tree<F: FieldExt>()
+    where
+        HashSpec: CachedSpec<F, 3, 2>,
+    {
+        let (trapdoor, nullifier) = (F::from_u64(2), F::from_u64(3));
+        let mut leaves: Vec<_> = (0..6).map(|i| F::from_u64(100 + i)).collect();
+        leaves[5] = identity_commitment(trapdoor, nullifier);
+        let tree = MerkleTree::from_leaves(MERKLE_DEPTH, &leaves);
+
+        let topic = b"topic";
+        let (circuit, public_inputs) =
+            SemaphoreCircuit::<F, MERKLE_DEPTH>::from_tree(trapdoor, nullifier, topic, &tree, 5);
+        assert_eq!(public_inputs.root, tree.root());
+
+        let prover = MockProver::run(10, &circuit, vec![public_inputs.to_instance()]).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // A verifier expecting another topic rejects the proof.
+        let other = PublicInputs::new(b"other topic", public_inputs.nullifier_hash, tree.root());
+        let prover = MockProver::run(10, &circuit, vec![other.to_instance()]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn from_tree() {
+        check_from_tree::<pallas::Base>();
+        check_from_tree::<vesta::Base>();
+    }
+}
@@ -0,0 +1,19 @@
+//! Runs the C harness in `tests/ffi/harness.c`, which `build.rs` compiles against
+//! `include/semaphore.h`, on the library's C ABI. Run it with
+//! `cargo test --features c-harness`.
+
+use std::os::raw::c_int;
+
+// Pull in the library, whose exported functions the harness calls.
+use halo2_semaphore as _;
+
+#[link(name = "semaphore_harness", kind = "static")]
+extern "C" {
+    fn semaphore_harness_main() -> c_int;
+}
+
+#[test]
+fn c_harness() {
+    let failures = unsafe { semaphore_harness_main() };
+    assert_eq!(failures, 0, "{} checks of the C harness failed", failures);
+}
@@ -0,0 +1,78 @@
+/* Exercises the C ABI end to end. Returns the number of failed checks. */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "semaphore.h"
+
+static int failures = 0;
+
+#define CHECK(cond)                                                  \
+  do {                                                               \
+    if (!(cond)) {                                                   \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+      failures++;                                                    \
+    }                                                                \
+  } while (0)
+
+int semaphore_harness_main(void) {
+  const char *secrets[3] = {"alice", "bob", "carol"};
+  SemaphoreBuffer identities[3];
+  uint8_t leaves[3 * 32];
+
+  for (int i = 0; i < 3; i++) {
+    CHECK(semaphore_identity_new((const uint8_t *)secrets[i], strlen(secrets[i]),
+                                 &identities[i]) == SEMAPHORE_OK);
+    CHECK(identities[i].len == 64);
+
+    SemaphoreBuffer commitment;
+    CHECK(semaphore_commitment(identities[i].data, identities[i].len, &commitment) ==
+          SEMAPHORE_OK);
+    CHECK(commitment.len == 32);
+    memcpy(leaves + 32 * i, commitment.data, 32);
+    semaphore_buffer_free(&commitment);
+    CHECK(commitment.data == NULL);
+  }
+
+  const char *topic = "https://example.com/poll/1";
+  SemaphoreBuffer proof;
+  CHECK(semaphore_prove(identities[1].data, identities[1].len, leaves, sizeof(leaves), 1,
+                        (const uint8_t *)topic, strlen(topic), &proof) == SEMAPHORE_OK);
+  CHECK(semaphore_verify(proof.data, proof.len) == SEMAPHORE_OK);
+
+  /* A tampered root fails verification. */
+  proof.data[1 + 32 + 2 * 32] ^= 1;
+  CHECK(semaphore_verify(proof.data, proof.len) == SEMAPHORE_INVALID_PROOF);
+  /* A tampered circuit id is reported as such. */
+  proof.data[1 + 32 + 2 * 32] ^= 1;
+  proof.data[1] ^= 1;
+  CHECK(semaphore_verify(proof.data, proof.len) == SEMAPHORE_CIRCUIT_MISMATCH);
+  CHECK(semaphore_verify(proof.data, 10) == SEMAPHORE_MALFORMED_PROOF);
+  semaphore_buffer_free(&proof);
+
+  /* Proving for another member's index is refused. */
+  SemaphoreBuffer refused = {NULL, 0};
+  CHECK(semaphore_prove(identities[0].data, identities[0].len, leaves, sizeof(leaves), 1,
+                        (const uint8_t *)topic, strlen(topic), &refused) ==
+        SEMAPHORE_NOT_MEMBER);
+  CHECK(refused.data == NULL);
+  CHECK(semaphore_commitment(identities[0].data, 63, &refused) == SEMAPHORE_INVALID_INPUT);
+
+  /* Once the first member is removed, its leaf is zero and the others can still prove. */
+  memset(leaves, 0, 32);
+  CHECK(semaphore_prove(identities[2].data, identities[2].len, leaves, sizeof(leaves), 2,
+                        (const uint8_t *)topic, strlen(topic), &proof) == SEMAPHORE_OK);
+  CHECK(semaphore_verify(proof.data, proof.len) == SEMAPHORE_OK);
+  semaphore_buffer_free(&proof);
+  CHECK(semaphore_prove(identities[0].data, identities[0].len, leaves, sizeof(leaves), 0,
+                        (const uint8_t *)topic, strlen(topic), &refused) ==
+        SEMAPHORE_NOT_MEMBER);
+  CHECK(semaphore_commitment(NULL, 64, &refused) == SEMAPHORE_NULL_POINTER);
+
+  for (int i = 0; i < 3; i++) {
+    semaphore_buffer_free(&identities[i]);
+  }
+  semaphore_buffer_free(NULL);
+
+  return failures;
+}